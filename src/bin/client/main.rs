@@ -1,29 +1,86 @@
+use chrono::{DateTime, Utc};
 use clap::{Parser, Subcommand};
 use luxnulla::{
-    CommandRequest, CommandResponse, ErrorCommandResponse, OkCommandResponse, SOCKET_NAME,
+    ConfigFile, DaemonStatus, GroupDetails, ServerRef,
+    client::{self, Client, ClientError, LogEvent, LogStream},
 };
-use std::{path::PathBuf, str::FromStr};
-use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::UnixStream,
+use std::{
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
 };
 
+/// Used when neither `$VISUAL`, `$EDITOR` nor `editor` in luxnulla.kdl is set.
+const DEFAULT_EDITOR: &str = "vi";
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
+    /// Socket of the daemon, `socket` in luxnulla.kdl by default
+    #[arg(long, global = true)]
+    socket: Option<PathBuf>,
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
+    /// Edit a config in $VISUAL or $EDITOR, the daemon checks it before it is saved
     Edit { target: EditTarget },
     Start,
-    Status,
+    Stop,
+    Status {
+        /// Print the raw status document instead of a summary
+        #[arg(long)]
+        json: bool,
+    },
     Restart,
+    /// Restore a previous core config
+    Rollback {
+        /// How many replaced configs to go back
+        #[arg(default_value_t = 1)]
+        steps: usize,
+    },
+    Logs {
+        /// Keep printing new lines as the core writes them
+        #[arg(short, long)]
+        follow: bool,
+        /// Number of recent lines to print first
+        #[arg(short = 'n', long, default_value_t = 100)]
+        lines: usize,
+    },
+    /// Manage groups of servers
+    Group {
+        #[command(subcommand)]
+        command: GroupCommand,
+    },
+    /// Route the core through a server of a group, the first one unless a name or index is given
+    Use {
+        group: String,
+        server: Option<ServerRef>,
+    },
+    /// Select the next server of the active group
+    Next,
+    /// Select the previous server of the active group
+    Prev,
     Tui,
 }
 
+#[derive(Subcommand, Debug)]
+enum GroupCommand {
+    /// List groups with the number of servers in each
+    List,
+    /// Show the servers of a group
+    Show { name: String },
+    /// Add a group from a subscription URL, a file of configs or `-` to read them from stdin
+    Add { name: String, source: String },
+    /// Remove a group
+    Rm { name: String },
+    Rename { name: String, new_name: String },
+    /// Fetch the subscription of a group again
+    Refresh { name: String },
+}
+
 #[derive(Debug, Clone)]
 enum EditTarget {
     Xray,
@@ -45,61 +102,322 @@ impl FromStr for EditTarget {
     }
 }
 
+impl EditTarget {
+    fn file(&self) -> ConfigFile {
+        match self {
+            EditTarget::Xray => ConfigFile::Core,
+            EditTarget::Luxnulla => ConfigFile::Luxnulla,
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    let mut sock = UnixStream::connect(PathBuf::from("/tmp").join(SOCKET_NAME)).await?;
+    let socket = args.socket.clone().unwrap_or_else(client::socket_path);
+    let mut client = Client::connect(&socket).await?;
 
-    let cmd: CommandRequest = request_action(args);
+    match args.command {
+        Commands::Edit { target } => return edit(&mut client, target.file()).await,
+        Commands::Start => println!("Ok: {}", client.start().await?),
+        Commands::Stop => println!("Ok: {}", client.stop().await?),
+        Commands::Restart => println!("Ok: {}", client.restart().await?),
+        Commands::Rollback { steps } => println!("Ok: {}", client.rollback(steps).await?),
+        Commands::Status { json } => {
+            let status = client.status().await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&status)?);
+            } else {
+                print_status(&status);
+            }
+        }
+        Commands::Logs {
+            follow: false,
+            lines,
+        } => {
+            for line in client.logs(lines).await? {
+                println!("{}", line);
+            }
+        }
+        Commands::Logs {
+            follow: true,
+            lines,
+        } => return print_logs(client.follow_logs(lines).await?).await,
+        Commands::Group { command } => group(&mut client, command).await?,
+        Commands::Use { group, server } => {
+            println!("Ok: {}", client.select(&group, server).await?)
+        }
+        Commands::Next => println!("Ok: {}", client.select_next().await?),
+        Commands::Prev => println!("Ok: {}", client.select_previous().await?),
+        Commands::Tui => {
+            eprintln!(
+                "Usage: client start|stop|status|restart|rollback|logs|edit|group|use|next|prev"
+            );
+            std::process::exit(1);
+        }
+    }
 
-    let out = serde_json::to_vec(&cmd)?;
-    sock.write_all(&out).await?;
+    Ok(())
+}
 
-    let mut buf = vec![0u8; 1024];
-    let n = sock.read(&mut buf).await?;
+/// Edits a copy of the file and sends it back, reopening the editor while the daemon
+/// rejects the edit and the user wants to fix it.
+async fn edit(client: &mut Client, file: ConfigFile) -> anyhow::Result<()> {
+    let config = client.read_config(file).await?;
 
-    let resp: CommandResponse = serde_json::from_slice(&buf[..n])?;
-    response_action(resp);
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .or(config.editor)
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
 
-    Ok(())
+    // the copy keeps the extension so the editor picks the right syntax
+    let path = std::env::temp_dir().join(format!(
+        "luxnulla-{}-{}",
+        std::process::id(),
+        config.name
+    ));
+    std::fs::write(&path, &config.content)?;
+
+    let result = edit_until_accepted(client, file, &editor, &path, &config.content).await;
+    let _ = std::fs::remove_file(&path);
+
+    result
 }
 
-fn request_action(args: Args) -> CommandRequest {
-    match args.command {
-        Commands::Edit { target } => match target {
-            EditTarget::Xray => CommandRequest::EditXray,
-            EditTarget::Luxnulla => CommandRequest::EditLuxnulla,
-        },
-
-        Commands::Start => CommandRequest::Start,
-        Commands::Status => CommandRequest::Status,
-        Commands::Restart => CommandRequest::Restart,
-        _ => {
-            eprintln!("Usage: client status|restart");
-            std::process::exit(1);
+async fn edit_until_accepted(
+    client: &mut Client,
+    file: ConfigFile,
+    editor: &str,
+    path: &Path,
+    original: &str,
+) -> anyhow::Result<()> {
+    loop {
+        // run through the shell, editors are often set with arguments such as `code --wait`
+        let status = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(path)
+            .status()
+            .await?;
+        if !status.success() {
+            anyhow::bail!("{} exited with {}", editor, status);
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        if content == original {
+            println!("No changes");
+            return Ok(());
+        }
+
+        match client.write_config(file, content).await {
+            Ok(message) => {
+                println!("Ok: {}", message);
+                return Ok(());
+            }
+            Err(ClientError::InvalidConfig(e)) => {
+                println!("Error: {}", e);
+
+                if !confirm("Edit again? [Y/n] ")? {
+                    println!("Changes discarded");
+                    return Ok(());
+                }
+            }
+            Err(e) => return Err(e.into()),
         }
     }
 }
 
-fn response_action(res: CommandResponse) {
-    match res {
-        CommandResponse::Ok(res) => match res {
-            OkCommandResponse::Message(msg) => {
-                println!("Ok: {}", msg);
+fn confirm(prompt: &str) -> io::Result<bool> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(!answer.trim().eq_ignore_ascii_case("n"))
+}
+
+async fn group(client: &mut Client, command: GroupCommand) -> anyhow::Result<()> {
+    match command {
+        GroupCommand::List => {
+            let groups = client.groups().await?;
+            if groups.is_empty() {
+                println!("No groups");
             }
-            OkCommandResponse::GetSubs(msg) => {
-                // println!("Error: {}", msg);
+            for group in groups {
+                print!(
+                    "{} ({} configs, refreshed {})",
+                    group.name,
+                    group.configs,
+                    format_refreshed(group.refreshed_at)
+                );
+                match group.source {
+                    Some(source) => println!(" from {}", source),
+                    None => println!(),
+                }
             }
-        },
+        }
+        GroupCommand::Show { name } => print_group(&client.group(&name).await?),
+        GroupCommand::Add { name, source } => {
+            let payload = read_source(&source)?;
+            println!("Ok: {}", client.add_group(&name, payload).await?);
+        }
+        GroupCommand::Rm { name } => println!("Ok: {}", client.remove_group(&name).await?),
+        GroupCommand::Rename { name, new_name } => {
+            println!("Ok: {}", client.rename_group(&name, &new_name).await?)
+        }
+        GroupCommand::Refresh { name } => println!("Ok: {}", client.refresh_group(&name).await?),
+    }
 
-        CommandResponse::Err(res) => match res {
-            ErrorCommandResponse::Message(err) => {
-                println!("Error: {}", err);
-            }
-            ErrorCommandResponse::GetSubs(msg) => {
-                println!("Error: {}", msg);
+    Ok(())
+}
+
+/// Configs to send for `group add`: stdin for `-`, the content of an existing file,
+/// otherwise the argument itself, such as a subscription URL the daemon fetches.
+fn read_source(source: &str) -> anyhow::Result<String> {
+    if source == "-" {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        return Ok(content);
+    }
+
+    let path = Path::new(source);
+    if path.is_file() {
+        return Ok(std::fs::read_to_string(path)?);
+    }
+
+    Ok(source.to_string())
+}
+
+fn print_group(group: &GroupDetails) {
+    println!(
+        "group:    {} ({} configs, refreshed {})",
+        group.name,
+        group.servers.len(),
+        format_refreshed(group.refreshed_at)
+    );
+    if let Some(source) = &group.source {
+        println!("source:   {}", source);
+    }
+
+    for (index, server) in group.servers.iter().enumerate() {
+        println!(
+            "{} {:>3}  {}  {} {}:{}",
+            if group.active == Some(index) { "*" } else { " " },
+            index,
+            server.name.as_deref().unwrap_or("-"),
+            server.protocol,
+            server.address,
+            server.port
+        );
+    }
+}
+
+/// Prints log lines and notifications until the daemon closes the connection.
+async fn print_logs(mut logs: LogStream) -> anyhow::Result<()> {
+    while let Some(event) = logs.next().await? {
+        match event {
+            LogEvent::Lines(lines) => {
+                for line in lines {
+                    println!("{}", line);
+                }
             }
-        },
+            LogEvent::Message(message) => println!("Ok: {}", message),
+            LogEvent::Error(error) => println!("Error: {}", error),
+        }
+    }
+
+    Ok(())
+}
+
+fn print_status(status: &DaemonStatus) {
+    println!(
+        "luxnulla-core {} up {}, http api on {}",
+        status.version,
+        format_duration(status.uptime_secs),
+        status.http_address
+    );
+
+    let core = &status.core;
+    print!("core:     {}", core.backend);
+    if let Some(version) = &core.version {
+        print!(" ({})", version);
+    }
+    print!(" {}", core.state);
+    if let Some(pid) = core.pid {
+        print!(" (pid {}", pid);
+        if let Some(uptime) = core.uptime_secs {
+            print!(", up {}", format_duration(uptime));
+        }
+        print!(")");
+    }
+    println!(
+        ", {} restarts, {} crashes",
+        core.restart_count, core.crash_count
+    );
+    if let Some(last_exit) = &core.last_exit {
+        println!("          last exit: {}", last_exit);
+    }
+
+    match &status.active {
+        Some(active) if active.server.is_some() => {
+            println!("active:   {} (#{})", active, active.index)
+        }
+        Some(active) => println!("active:   {}", active),
+        None => println!("active:   none"),
+    }
+
+    for inbound in &status.inbounds {
+        println!(
+            "inbound:  {} {}:{}{}",
+            inbound.protocol,
+            inbound.listen,
+            inbound.port,
+            inbound
+                .tag
+                .as_ref()
+                .map(|tag| format!(" ({})", tag))
+                .unwrap_or_default()
+        );
+    }
+
+    if status.groups.is_empty() {
+        println!("groups:   none");
+    }
+    for group in &status.groups {
+        println!(
+            "group:    {} ({} configs, refreshed {})",
+            group.name,
+            group.configs,
+            format_refreshed(group.refreshed_at)
+        );
+    }
+}
+
+fn format_refreshed(at: Option<DateTime<Utc>>) -> String {
+    at.map(|at| at.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| String::from("never"))
+}
+
+fn format_duration(secs: u64) -> String {
+    let (days, hours, minutes, seconds) = (
+        secs / 86_400,
+        secs % 86_400 / 3_600,
+        secs % 3_600 / 60,
+        secs % 60,
+    );
+
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
     }
 }
@@ -1,38 +1,113 @@
-use luxnulla::{CommandRequest, CommandResponse, ErrorCommandResponse};
-use std::path::PathBuf;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::UnixStream;
+use luxnulla::{
+    CommandRequest, CommandResponse, ErrorCommandResponse, Hello, HelloResponse,
+    OkCommandResponse, PROTOCOL_VERSION, Response,
+    framing::{FrameError, FrameReader, write_frame},
+};
+use serde_json::Value;
+use std::{path::PathBuf, sync::Arc};
+use tokio::net::{
+    UnixStream,
+    unix::{OwnedReadHalf, OwnedWriteHalf},
+};
+use tokio::sync::broadcast::error::RecvError;
 
-use crate::handlers::CommandHandler;
+use crate::{
+    handlers::CommandHandler,
+    services::{Settings, StorageService},
+};
 
 pub struct ClientHandler {
     command_handler: CommandHandler,
 }
 
 impl ClientHandler {
-    pub fn new(config_dir: PathBuf) -> Self {
+    pub fn new(config_dir: PathBuf, storage: Arc<StorageService>, settings: Settings) -> Self {
         Self {
-            command_handler: CommandHandler::new(config_dir),
+            command_handler: CommandHandler::new(config_dir, storage, settings),
         }
     }
 
-    pub async fn handle_client(&self, mut sock: UnixStream) {
-        let mut buf = vec![0u8; 1024];
-        match sock.read(&mut buf).await {
-            Ok(n) if n > 0 => {
-                let response = match serde_json::from_slice::<CommandRequest>(&buf[..n]) {
-                    Ok(request) => self.command_handler.handle_command(request).await,
-                    Err(e) => CommandResponse::Err(ErrorCommandResponse::Message(format!(
-                        "bad request: {}",
-                        e
-                    ))),
-                };
-
-                if let Ok(output) = serde_json::to_vec(&response) {
-                    let _ = sock.write_all(&output).await;
+    /// Answers requests until the client hangs up, a request to follow the log keeps
+    /// the connection for the stream.
+    pub async fn handle_client(&self, sock: UnixStream) {
+        let max_size = self.command_handler.settings().borrow().max_message_size;
+        let (reader, mut writer) = sock.into_split();
+        let mut requests = FrameReader::with_max_size(reader, max_size);
+
+        if !handshake(&mut requests, &mut writer).await {
+            return;
+        }
+
+        loop {
+            let (id, result) = match requests.read::<Value>().await {
+                Ok(Some(message)) => read_request(message),
+                Ok(None) | Err(FrameError::Io(_)) => return,
+                Err(e) => (0, Err(e)),
+            };
+
+            let response = match result {
+                Ok(CommandRequest::Logs {
+                    follow: true,
+                    lines,
+                }) => return self.follow_logs(writer, id, lines).await,
+                Ok(request) => self.command_handler.handle_command(request).await,
+                Err(e) => {
+                    let response = Response {
+                        id,
+                        response: CommandResponse::Err(ErrorCommandResponse::Message(format!(
+                            "bad request: {}",
+                            e
+                        ))),
+                    };
+                    let _ = write_frame(&mut writer, &response).await;
+
+                    // the rest of an oversized message cannot be told apart from the next one
+                    match e {
+                        FrameError::TooLarge { .. } => return,
+                        _ => continue,
+                    }
                 }
+            };
+
+            if write_frame(&mut writer, &Response { id, response }).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Sends the tail of the log and then every new line, one JSON response per line of output,
+    /// until the client hangs up. Reload notifications are sent in between as they happen.
+    async fn follow_logs(&self, mut sock: OwnedWriteHalf, id: u64, lines: usize) {
+        let (tail, mut receiver) = self.command_handler.xray_logs().follow(lines);
+        let mut notifications = self.command_handler.notifications();
+
+        let tail = Response {
+            id,
+            response: CommandResponse::Ok(OkCommandResponse::Logs(tail)),
+        };
+        if write_frame(&mut sock, &tail).await.is_err() {
+            return;
+        }
+
+        loop {
+            let response = tokio::select! {
+                line = receiver.recv() => match line {
+                    Ok(line) => CommandResponse::Ok(OkCommandResponse::Logs(vec![line])),
+                    Err(RecvError::Lagged(skipped)) => CommandResponse::Ok(OkCommandResponse::Logs(
+                        vec![format!("luxnulla: skipped {} lines", skipped)],
+                    )),
+                    Err(RecvError::Closed) => return,
+                },
+                notification = notifications.recv() => match notification {
+                    Ok(notification) => notification,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return,
+                },
+            };
+
+            if write_frame(&mut sock, &Response { id, response }).await.is_err() {
+                return;
             }
-            _ => {}
         }
     }
 
@@ -40,3 +115,37 @@ impl ClientHandler {
         &self.command_handler
     }
 }
+
+/// Answers the hello of the client, `false` when the connection cannot go on.
+async fn handshake(requests: &mut FrameReader<OwnedReadHalf>, writer: &mut OwnedWriteHalf) -> bool {
+    let refusal = match requests.read::<Hello>().await {
+        Ok(Some(hello)) => match hello.check() {
+            Ok(()) => None,
+            Err(e) => Some(e.to_string()),
+        },
+        Ok(None) | Err(FrameError::Io(_)) => return false,
+        Err(e) => Some(format!(
+            "expected a hello for protocol {}: {}",
+            PROTOCOL_VERSION, e
+        )),
+    };
+
+    let response = match refusal {
+        Some(reason) => HelloResponse::Err(reason),
+        None => HelloResponse::Ok(Hello::current()),
+    };
+
+    write_frame(writer, &response).await.is_ok() && matches!(response, HelloResponse::Ok(_))
+}
+
+/// Splits a message into its ID and request, so a request this build does not know
+/// is still answered under its ID.
+fn read_request(mut message: Value) -> (u64, Result<CommandRequest, FrameError>) {
+    let id = message.get("id").and_then(Value::as_u64).unwrap_or(0);
+    let request = message
+        .get_mut("request")
+        .map(Value::take)
+        .unwrap_or(Value::Null);
+
+    (id, serde_json::from_value(request).map_err(FrameError::from))
+}
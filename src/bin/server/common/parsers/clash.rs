@@ -0,0 +1,467 @@
+use serde_yaml::{Mapping, Value as YamlValue};
+use url::Url;
+
+use crate::common::parsers::{
+    fields::{Fields, server_url},
+    proxy_config::{self, ParseError, ParseReport, append_opt, set_user_info},
+};
+
+// example clash / clash.meta subscription
+// proxies:
+//   - name: "vless-reality"
+//     type: vless
+//     server: 24.120.32.42
+//     port: 443
+//     uuid: d8737518-5251-4e25-a653-8c625ef18b8f
+//     network: grpc
+//     tls: true
+//     servername: unpkg.com
+//     flow: xtls-rprx-vision
+//     client-fingerprint: chrome
+//     reality-opts:
+//       public-key: FPIcpZmVrQcqkF1vR_aBnLw_Uu4CNhuuKkrRtKpzRHg
+//       short-id: e0969a6f81b52865
+//     grpc-opts:
+//       grpc-service-name: grpc
+
+/// Clash documents are recognised by a top level `proxies` list.
+pub fn is_clash_config(payload: &str) -> bool {
+    if !payload.contains("proxies:") {
+        return false;
+    }
+
+    serde_yaml::from_str::<Mapping>(payload)
+        .ok()
+        .and_then(|document| document.get("proxies").map(YamlValue::is_sequence))
+        .unwrap_or(false)
+}
+
+/// Converts every entry of the `proxies` list, each one is reported by its position in the list.
+pub fn work(payload: &str) -> Result<ParseReport, serde_yaml::Error> {
+    let document: Mapping = serde_yaml::from_str(payload)?;
+    let mut report = ParseReport::default();
+
+    let proxies = document
+        .get("proxies")
+        .and_then(YamlValue::as_sequence)
+        .cloned()
+        .unwrap_or_default();
+
+    for (index, proxy) in proxies.iter().enumerate() {
+        let Some(proxy) = proxy.as_mapping() else {
+            report.reject(
+                index + 1,
+                None,
+                String::from("<not a mapping>"),
+                ParseError::UnknownFieldType {
+                    current: "proxy entry".to_string(),
+                    expected: "mapping".to_string(),
+                },
+            );
+            continue;
+        };
+
+        let proxy_type = proxy.get_str("type");
+
+        match to_share_uri(proxy) {
+            Ok(uri) => report.push_line(index + 1, &uri),
+            Err(reason) => report.reject(
+                index + 1,
+                proxy_type.clone(),
+                format!(
+                    "{}: {}",
+                    proxy_type.as_deref().unwrap_or("unknown"),
+                    proxy.get_str("name").unwrap_or_default()
+                ),
+                reason,
+            ),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Writes a clash proxy as a share link so it goes through the same parsers as subscriptions.
+fn to_share_uri(proxy: &Mapping) -> Result<String, ParseError> {
+    let proxy_type = proxy.require("type")?;
+    let server = proxy.require("server")?;
+    let port = proxy.require("port")?;
+
+    let scheme = match proxy_type.as_str() {
+        "vless" | "vmess" | "trojan" | "tuic" => proxy_type.as_str(),
+        "ss" | "shadowsocks" => "ss",
+        "hysteria2" | "hy2" => "hysteria2",
+        other => return Err(ParseError::UnknownScheme(other.to_string())),
+    };
+
+    let mut url = server_url(scheme, &server, port)?;
+
+    match scheme {
+        "vless" => {
+            set_user_info(&mut url, &proxy.require("uuid")?, None)?;
+            let mut query = url.query_pairs_mut();
+            append_opt(&mut query, "encryption", proxy.get_str("encryption"));
+            append_opt(&mut query, "flow", proxy.get_str("flow"));
+            drop(query);
+            append_stream(&mut url, proxy, "servername", false);
+        }
+        "vmess" => {
+            set_user_info(&mut url, &proxy.require("uuid")?, None)?;
+            let mut query = url.query_pairs_mut();
+            append_opt(&mut query, "aid", proxy.get_str("alterId"));
+            append_opt(&mut query, "encryption", proxy.get_str("cipher"));
+            drop(query);
+            append_stream(&mut url, proxy, "servername", false);
+        }
+        "trojan" => {
+            set_user_info(&mut url, &proxy.require("password")?, None)?;
+            append_stream(&mut url, proxy, "sni", true);
+        }
+        "ss" => {
+            set_user_info(
+                &mut url,
+                &proxy.require("cipher")?,
+                Some(&proxy.require("password")?),
+            )?;
+            if let Some(plugin) = shadowsocks_plugin(proxy) {
+                url.set_path("/");
+                url.query_pairs_mut().append_pair("plugin", &plugin);
+            }
+        }
+        "hysteria2" => {
+            set_user_info(&mut url, &proxy.require("password")?, None)?;
+            let mut query = url.query_pairs_mut();
+            append_opt(&mut query, "sni", proxy.get_str("sni"));
+            if proxy.get_bool("skip-cert-verify") == Some(true) {
+                query.append_pair("insecure", "1");
+            }
+            append_opt(&mut query, "obfs", proxy.get_str("obfs"));
+            append_opt(&mut query, "obfs-password", proxy.get_str("obfs-password"));
+            append_opt(&mut query, "up", proxy.get_str("up"));
+            append_opt(&mut query, "down", proxy.get_str("down"));
+            append_opt(&mut query, "alpn", proxy.get_list("alpn"));
+        }
+        "tuic" => {
+            set_user_info(
+                &mut url,
+                &proxy.require("uuid")?,
+                Some(&proxy.require("password")?),
+            )?;
+            let mut query = url.query_pairs_mut();
+            append_opt(
+                &mut query,
+                "congestion_control",
+                proxy.get_str("congestion-controller"),
+            );
+            append_opt(&mut query, "udp_relay_mode", proxy.get_str("udp-relay-mode"));
+            append_opt(&mut query, "alpn", proxy.get_list("alpn"));
+            append_opt(&mut query, "sni", proxy.get_str("sni"));
+            if proxy.get_bool("skip-cert-verify") == Some(true) {
+                query.append_pair("allow_insecure", "1");
+            }
+        }
+        _ => unreachable!(),
+    }
+
+    if url.query() == Some("") {
+        url.set_query(None);
+    }
+
+    if let Some(name) = proxy.get_str("name") {
+        url.set_fragment(Some(&proxy_config::encode_component(&name)));
+    }
+
+    Ok(url.to_string())
+}
+
+/// Translates `network`, `*-opts`, `tls` and `reality-opts` into share link parameters.
+fn append_stream(url: &mut Url, proxy: &Mapping, sni_key: &str, default_tls: bool) {
+    let network = proxy.get_str("network").unwrap_or_else(|| "tcp".to_string());
+    let mut query = url.query_pairs_mut();
+
+    match network.as_str() {
+        "ws" => {
+            let opts = proxy.get_map("ws-opts");
+            let upgrade = opts
+                .and_then(|opts| opts.get_bool("v2ray-http-upgrade"))
+                .unwrap_or(false);
+
+            query.append_pair("type", if upgrade { "httpupgrade" } else { "ws" });
+            append_opt(&mut query, "path", opts.and_then(|opts| opts.get_str("path")));
+            append_opt(&mut query, "host", opts.and_then(Fields::header_host));
+        }
+        "grpc" => {
+            let opts = proxy.get_map("grpc-opts");
+
+            query.append_pair("type", "grpc");
+            append_opt(
+                &mut query,
+                "serviceName",
+                opts.and_then(|opts| opts.get_str("grpc-service-name")),
+            );
+        }
+        "http" => {
+            let opts = proxy.get_map("http-opts");
+
+            query.append_pair("type", "tcp");
+            query.append_pair("headerType", "http");
+            append_opt(&mut query, "path", opts.and_then(|opts| opts.get_list("path")));
+            append_opt(&mut query, "host", opts.and_then(Fields::header_host));
+        }
+        "xhttp" => {
+            let opts = proxy.get_map("xhttp-opts");
+
+            query.append_pair("type", "xhttp");
+            append_opt(&mut query, "path", opts.and_then(|opts| opts.get_str("path")));
+            append_opt(&mut query, "host", opts.and_then(|opts| opts.get_str("host")));
+            append_opt(&mut query, "mode", opts.and_then(|opts| opts.get_str("mode")));
+        }
+        // h2 and anything else is left for the transport parser to reject
+        other => {
+            query.append_pair("type", other);
+        }
+    }
+
+    let reality = proxy.get_map("reality-opts");
+    let tls = proxy.get_bool("tls").unwrap_or(default_tls);
+
+    if let Some(reality) = reality {
+        query.append_pair("security", "reality");
+        append_opt(&mut query, "pbk", reality.get_str("public-key"));
+        append_opt(&mut query, "sid", reality.get_str("short-id"));
+    } else if tls {
+        query.append_pair("security", "tls");
+    } else {
+        query.append_pair("security", "none");
+    }
+
+    if reality.is_some() || tls {
+        append_opt(&mut query, "sni", proxy.get_str(sni_key));
+        append_opt(&mut query, "alpn", proxy.get_list("alpn"));
+        append_opt(&mut query, "fp", proxy.get_str("client-fingerprint"));
+        if proxy.get_bool("skip-cert-verify") == Some(true) {
+            query.append_pair("allowInsecure", "1");
+        }
+    }
+}
+
+/// Rebuilds the SIP003 `plugin=name;opts` value from clash `plugin` and `plugin-opts`.
+fn shadowsocks_plugin(proxy: &Mapping) -> Option<String> {
+    let plugin = proxy.get_str("plugin")?;
+    let opts = proxy.get_map("plugin-opts");
+    let opt = |key: &str| opts.and_then(|opts| opts.get_str(key));
+
+    let (name, mut params) = match plugin.as_str() {
+        "obfs" => (
+            "obfs-local",
+            vec![
+                opt("mode").map(|mode| format!("obfs={}", mode)),
+                opt("host").map(|host| format!("obfs-host={}", host)),
+            ],
+        ),
+        "v2ray-plugin" => (
+            "v2ray-plugin",
+            vec![
+                opt("mode").map(|mode| format!("mode={}", mode)),
+                opt("host").map(|host| format!("host={}", host)),
+                opt("path").map(|path| format!("path={}", path)),
+                opts.and_then(|opts| opts.get_bool("tls"))
+                    .filter(|tls| *tls)
+                    .map(|_| String::from("tls")),
+            ],
+        ),
+        other => (other, Vec::new()),
+    };
+
+    params.insert(0, Some(name.to_string()));
+
+    Some(params.into_iter().flatten().collect::<Vec<_>>().join(";"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(yaml: &str) -> ParseReport {
+        work(yaml).unwrap().assert_all_accepted()
+    }
+
+    #[test]
+    fn converts_vless_reality_grpc() {
+        let report = convert(
+            r#"
+proxies:
+  - name: "vless-reality"
+    type: vless
+    server: 24.120.32.42
+    port: 443
+    uuid: d8737518-5251-4e25-a653-8c625ef18b8f
+    network: grpc
+    tls: true
+    servername: unpkg.com
+    flow: xtls-rprx-vision
+    client-fingerprint: chrome
+    reality-opts:
+      public-key: FPIcpZmVrQcqkF1vR_aBnLw_Uu4CNhuuKkrRtKpzRHg
+      short-id: e0969a6f81b52865
+    grpc-opts:
+      grpc-service-name: grpc
+"#,
+        );
+        let config = &report.configs[0];
+
+        assert_eq!(config.protocol(), "vless");
+        assert_eq!(config.name(), Some("vless-reality"));
+        assert_eq!((config.address(), config.port()), ("24.120.32.42", 443));
+        assert_eq!(config.user().unwrap().flow.as_deref(), Some("xtls-rprx-vision"));
+        assert_eq!((config.network(), config.path()), (Some("grpc"), Some("grpc")));
+        assert_eq!(config.security(), Some("reality"));
+
+        let reality = config.reality_settings().unwrap();
+        assert_eq!(reality.server_name, "unpkg.com");
+        assert_eq!(reality.short_id, "e0969a6f81b52865");
+        assert_eq!(reality.fingerprint.as_deref(), Some("chrome"));
+    }
+
+    #[test]
+    fn converts_transport_options() {
+        let report = convert(
+            r#"
+proxies:
+  - { name: ws, type: vmess, server: a.com, port: 443, uuid: id, alterId: 0, cipher: auto,
+      tls: true, servername: s.com, network: ws,
+      ws-opts: { path: /ws, headers: { Host: cdn.com } } }
+  - { name: up, type: trojan, server: b.com, port: 443, password: pw, network: ws,
+      ws-opts: { path: /up, v2ray-http-upgrade: true, headers: { Host: up.com } } }
+  - { name: http, type: vless, server: c.com, port: 80, uuid: id, network: http,
+      http-opts: { path: [/a, /b], headers: { Host: [h.com] } } }
+  - { name: xhttp, type: vless, server: d.com, port: 443, uuid: id, tls: true, network: xhttp,
+      xhttp-opts: { path: /x, host: x.com, mode: packet-up } }
+"#,
+        );
+        let transports: Vec<_> = report
+            .configs
+            .iter()
+            .map(|config| (config.network(), config.path(), config.host()))
+            .collect();
+
+        assert_eq!(
+            transports,
+            vec![
+                (Some("ws"), Some("/ws"), Some("cdn.com")),
+                (Some("httpupgrade"), Some("/up"), Some("up.com")),
+                (Some("tcp"), Some("/a"), Some("h.com")),
+                (Some("xhttp"), Some("/x"), Some("x.com")),
+            ]
+        );
+        assert_eq!(
+            report.configs[0].tls_settings().unwrap().server_name.as_deref(),
+            Some("s.com")
+        );
+        // trojan is tls unless it says otherwise
+        assert_eq!(report.configs[1].security(), Some("tls"));
+        assert_eq!(report.configs[2].security(), None);
+    }
+
+    #[test]
+    fn rebuilds_shadowsocks_plugins() {
+        let report = convert(
+            r#"
+proxies:
+  - name: obfs
+    type: ss
+    server: 1.2.3.4
+    port: 8388
+    cipher: chacha20-ietf-poly1305
+    password: secret
+    plugin: obfs
+    plugin-opts: { mode: http, host: example.com }
+  - name: v2ray
+    type: ss
+    server: 1.2.3.4
+    port: 443
+    cipher: aes-128-gcm
+    password: secret
+    plugin: v2ray-plugin
+    plugin-opts: { mode: websocket, host: example.com, path: /ws, tls: true }
+"#,
+        );
+        let plugins: Vec<_> = report
+            .configs
+            .iter()
+            .map(|config| (config.plugin(), config.plugin_opts()))
+            .collect();
+
+        assert_eq!(
+            plugins,
+            vec![
+                (Some("obfs-local"), Some("obfs=http;obfs-host=example.com")),
+                (
+                    Some("v2ray-plugin"),
+                    Some("mode=websocket;host=example.com;path=/ws;tls")
+                ),
+            ]
+        );
+        assert_eq!(report.configs[0].method(), Some("chacha20-ietf-poly1305"));
+    }
+
+    #[test]
+    fn converts_hysteria2_and_tuic() {
+        let report = convert(
+            r#"
+proxies:
+  - { name: hy, type: hysteria2, server: h.com, port: 443, password: pw, sni: s.com,
+      skip-cert-verify: true, obfs: salamander, obfs-password: x, up: 50, down: 100,
+      alpn: [h3] }
+  - { name: tuic, type: tuic, server: t.com, port: 443, uuid: id, password: pw,
+      congestion-controller: bbr, udp-relay-mode: native, alpn: [h3], sni: t.com }
+"#,
+        );
+        let (hysteria2, tuic) = (&report.configs[0], &report.configs[1]);
+
+        assert_eq!(hysteria2.protocol(), "hysteria2");
+        assert!(hysteria2.insecure());
+        assert_eq!((hysteria2.obfs(), hysteria2.obfs_password()), (Some("salamander"), Some("x")));
+        assert_eq!((hysteria2.up_mbps(), hysteria2.down_mbps()), (Some(50), Some(100)));
+        assert_eq!(hysteria2.alpn(), ["h3"]);
+
+        assert_eq!(tuic.protocol(), "tuic");
+        assert_eq!((tuic.uuid(), tuic.password()), (Some("id"), Some("pw")));
+        assert_eq!(tuic.congestion_control(), Some("bbr"));
+        assert_eq!(tuic.udp_relay_mode(), Some("native"));
+        assert_eq!(tuic.sni(), Some("t.com"));
+    }
+
+    #[test]
+    fn rejects_unsupported_and_incomplete_entries() {
+        let report = work(
+            r#"
+proxies:
+  - { name: ok, type: trojan, server: a.com, port: 443, password: pw }
+  - { name: socks, type: socks5, server: b.com, port: 1080 }
+  - { name: no-uuid, type: vless, server: c.com, port: 443 }
+  - just a string
+  - { name: no-key, type: vless, server: d.com, port: 443, uuid: id, servername: s.com,
+      reality-opts: { short-id: ab } }
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(report.accepted_count(), 1);
+        assert_eq!(
+            report.rejections(),
+            vec![
+                (2, ParseError::UnknownScheme("socks5".to_string())),
+                (3, ParseError::FieldMissing("uuid".to_string())),
+                (
+                    4,
+                    ParseError::UnknownFieldType {
+                        current: "proxy entry".to_string(),
+                        expected: "mapping".to_string(),
+                    }
+                ),
+                (5, ParseError::FieldMissing("pbk".to_string())),
+            ]
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use serde_json::{Map, Value as JsonValue};
+use serde_yaml::{Mapping, Value as YamlValue};
+use std::fmt::Display;
+use url::Url;
+
+use crate::common::parsers::proxy_config::ParseError;
+
+/// Lookups the converters of clash YAML and JSON outbounds share, so both read a field the
+/// same way before it is written into a share link.
+pub trait Fields {
+    /// Non-empty strings, numbers and booleans as text.
+    fn get_str(&self, key: &str) -> Option<String>;
+
+    /// Booleans, also written as `"true"` or `"false"`.
+    fn get_bool(&self, key: &str) -> Option<bool>;
+
+    /// Lists are joined with commas the way share links carry them, plain strings are kept.
+    fn get_list(&self, key: &str) -> Option<String>;
+
+    fn get_map(&self, key: &str) -> Option<&Self>;
+
+    fn require(&self, key: &str) -> Result<String, ParseError> {
+        self.get_str(key)
+            .ok_or(ParseError::FieldMissing(key.to_string()))
+    }
+
+    /// `headers.Host`, either a string or a list.
+    fn header_host(&self) -> Option<String> {
+        self.get_map("headers")
+            .and_then(|headers| headers.get_list("Host"))
+    }
+}
+
+impl Fields for Mapping {
+    fn get_str(&self, key: &str) -> Option<String> {
+        match self.get(key)? {
+            YamlValue::String(value) if !value.is_empty() => Some(value.clone()),
+            YamlValue::Number(value) => Some(value.to_string()),
+            YamlValue::Bool(value) => Some(value.to_string()),
+            _ => None,
+        }
+    }
+
+    fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key)? {
+            YamlValue::Bool(value) => Some(*value),
+            YamlValue::String(value) => value.parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn get_list(&self, key: &str) -> Option<String> {
+        match self.get(key)? {
+            YamlValue::Sequence(values) => {
+                join(values.iter().filter_map(|value| value.as_str()))
+            }
+            _ => self.get_str(key),
+        }
+    }
+
+    fn get_map(&self, key: &str) -> Option<&Self> {
+        self.get(key).and_then(YamlValue::as_mapping)
+    }
+}
+
+impl Fields for Map<String, JsonValue> {
+    fn get_str(&self, key: &str) -> Option<String> {
+        match self.get(key)? {
+            JsonValue::String(value) if !value.is_empty() => Some(value.clone()),
+            JsonValue::Number(value) => Some(value.to_string()),
+            JsonValue::Bool(value) => Some(value.to_string()),
+            _ => None,
+        }
+    }
+
+    fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key)? {
+            JsonValue::Bool(value) => Some(*value),
+            JsonValue::String(value) => value.parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn get_list(&self, key: &str) -> Option<String> {
+        match self.get(key)? {
+            JsonValue::Array(values) => join(values.iter().filter_map(JsonValue::as_str)),
+            _ => self.get_str(key),
+        }
+    }
+
+    fn get_map(&self, key: &str) -> Option<&Self> {
+        self.get(key).and_then(JsonValue::as_object)
+    }
+}
+
+/// Share link of `scheme` pointing at `server`, IPv6 addresses are put in brackets.
+pub fn server_url(scheme: &str, server: &str, port: impl Display) -> Result<Url, ParseError> {
+    let host = if server.contains(':') && !server.starts_with('[') {
+        format!("[{}]", server)
+    } else {
+        server.to_string()
+    };
+
+    Url::parse(&format!("{}://{}:{}", scheme, host, port))
+        .map_err(|err| ParseError::InvalidUrl(err.to_string()))
+}
+
+fn join<'a>(values: impl Iterator<Item = &'a str>) -> Option<String> {
+    let values: Vec<&str> = values.collect();
+
+    (!values.is_empty()).then(|| values.join(","))
+}
@@ -1 +1,5 @@
+pub mod clash;
+pub mod fields;
+pub mod outbounds;
 pub mod proxy_config;
+pub mod sip008;
@@ -0,0 +1,647 @@
+use serde_json::{Map, Value as JsonValue};
+use url::Url;
+
+use crate::common::parsers::{
+    fields::{Fields, server_url},
+    proxy_config::{self, ParseError, ParseReport, append_opt, set_user_info},
+};
+
+type JsonObject = Map<String, JsonValue>;
+type Converter = fn(&JsonObject) -> Result<String, ParseError>;
+
+// example xray outbound
+// {
+//   "protocol": "vless", "tag": "proxy",
+//   "settings": { "vnext": [{ "address": "24.120.32.42", "port": 443,
+//                             "users": [{ "id": "d8737518-...", "flow": "xtls-rprx-vision" }] }] },
+//   "streamSettings": { "network": "grpc", "security": "reality",
+//                       "realitySettings": { "serverName": "unpkg.com", "publicKey": "...", "shortId": "..." },
+//                       "grpcSettings": { "serviceName": "grpc" } }
+// }
+//
+// example sing-box outbound
+// {
+//   "type": "vless", "tag": "proxy", "server": "24.120.32.42", "server_port": 443,
+//   "uuid": "d8737518-...", "flow": "xtls-rprx-vision",
+//   "tls": { "enabled": true, "server_name": "unpkg.com", "utls": { "enabled": true, "fingerprint": "chrome" },
+//            "reality": { "enabled": true, "public_key": "...", "short_id": "..." } },
+//   "transport": { "type": "grpc", "service_name": "grpc" }
+// }
+
+/// Outbounds that route traffic somewhere other than a proxy server.
+const SKIPPED_OUTBOUNDS: &[&str] = &[
+    "freedom",
+    "blackhole",
+    "dns",
+    "loopback",
+    "direct",
+    "block",
+    "selector",
+    "urltest",
+];
+
+/// Both xray and sing-box configs keep their proxies in a top level `outbounds` array.
+pub fn is_outbounds_config(payload: &str) -> bool {
+    let payload = payload.trim();
+    if !payload.starts_with('{') {
+        return false;
+    }
+
+    serde_json::from_str::<JsonObject>(payload)
+        .ok()
+        .and_then(|document| document.get("outbounds").map(JsonValue::is_array))
+        .unwrap_or(false)
+}
+
+/// Converts every proxy outbound, each one is reported by its position in the array.
+pub fn work(payload: &str) -> Result<ParseReport, serde_json::Error> {
+    let document: JsonObject = serde_json::from_str(payload.trim())?;
+    let mut report = ParseReport::default();
+
+    let outbounds = document
+        .get("outbounds")
+        .and_then(JsonValue::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    for (index, outbound) in outbounds.iter().enumerate() {
+        let Some(outbound) = outbound.as_object() else {
+            report.reject(
+                index + 1,
+                None,
+                String::from("<not an object>"),
+                ParseError::UnknownFieldType {
+                    current: "outbound".to_string(),
+                    expected: "object".to_string(),
+                },
+            );
+            continue;
+        };
+
+        // xray names the protocol `protocol`, sing-box names it `type`
+        let (protocol, convert): (String, Converter) =
+            match (outbound.get_str("protocol"), outbound.get_str("type")) {
+                (Some(protocol), _) => (protocol, xray_to_share_uri),
+                (None, Some(protocol)) => (protocol, sing_box_to_share_uri),
+                (None, None) => {
+                    report.reject(
+                        index + 1,
+                        None,
+                        outbound.get_str("tag").unwrap_or_default(),
+                        ParseError::FieldMissing("protocol".to_string()),
+                    );
+                    continue;
+                }
+            };
+
+        if SKIPPED_OUTBOUNDS.contains(&protocol.as_str()) {
+            continue;
+        }
+
+        match convert(outbound) {
+            Ok(uri) => report.push_line(index + 1, &uri),
+            Err(reason) => report.reject(
+                index + 1,
+                Some(protocol.clone()),
+                format!(
+                    "{}: {}",
+                    protocol,
+                    outbound.get_str("tag").unwrap_or_default()
+                ),
+                reason,
+            ),
+        }
+    }
+
+    Ok(report)
+}
+
+fn finish(mut url: Url, name: Option<String>) -> String {
+    if url.query() == Some("") {
+        url.set_query(None);
+    }
+
+    if let Some(name) = name {
+        url.set_fragment(Some(&proxy_config::encode_component(&name)));
+    }
+
+    url.to_string()
+}
+
+fn xray_to_share_uri(outbound: &JsonObject) -> Result<String, ParseError> {
+    let protocol = outbound.require("protocol")?;
+    let settings = outbound
+        .get_map("settings")
+        .ok_or(ParseError::FieldMissing("settings".to_string()))?;
+
+    let (scheme, server) = match protocol.as_str() {
+        "vless" | "vmess" => (protocol.as_str(), first_of(settings, "vnext")?),
+        "trojan" => ("trojan", first_of(settings, "servers")?),
+        "shadowsocks" => ("ss", first_of(settings, "servers")?),
+        other => return Err(ParseError::UnknownScheme(other.to_string())),
+    };
+
+    let mut url = server_url(
+        scheme,
+        &server.require("address")?,
+        &server.require("port")?,
+    )?;
+
+    match scheme {
+        "vless" | "vmess" => {
+            let user = get_array(server, "users")
+                .and_then(|users| users.first())
+                .and_then(JsonValue::as_object)
+                .ok_or(ParseError::FieldMissing("users".to_string()))?;
+
+            set_user_info(&mut url, &user.require("id")?, None)?;
+
+            let mut query = url.query_pairs_mut();
+            if scheme == "vless" {
+                append_opt(&mut query, "encryption", user.get_str("encryption"));
+                append_opt(&mut query, "flow", user.get_str("flow"));
+            } else {
+                append_opt(&mut query, "aid", user.get_str("alterId"));
+                append_opt(&mut query, "encryption", user.get_str("security"));
+            }
+        }
+        "trojan" => {
+            set_user_info(&mut url, &server.require("password")?, None)?;
+        }
+        _ => {
+            set_user_info(
+                &mut url,
+                &server.require("method")?,
+                Some(&server.require("password")?),
+            )?;
+        }
+    }
+
+    if scheme != "ss" {
+        let stream = outbound.get_map("streamSettings");
+        append_xray_stream(&mut url, stream);
+    }
+
+    Ok(finish(url, outbound.get_str("tag")))
+}
+
+fn append_xray_stream(url: &mut Url, stream: Option<&JsonObject>) {
+    let empty = JsonObject::new();
+    let stream = stream.unwrap_or(&empty);
+    let network = stream.get_str("network").unwrap_or_else(|| "tcp".to_string());
+    let mut query = url.query_pairs_mut();
+
+    match network.as_str() {
+        "ws" => {
+            let ws = stream.get_map("wsSettings");
+            query.append_pair("type", "ws");
+            append_opt(&mut query, "path", ws.and_then(|ws| ws.get_str("path")));
+            append_opt(
+                &mut query,
+                "host",
+                ws.and_then(|ws| ws.get_str("host").or_else(|| ws.header_host())),
+            );
+        }
+        "grpc" | "gun" => {
+            let grpc = stream.get_map("grpcSettings");
+            query.append_pair("type", "grpc");
+            append_opt(
+                &mut query,
+                "serviceName",
+                grpc.and_then(|grpc| grpc.get_str("serviceName")),
+            );
+            append_opt(
+                &mut query,
+                "authority",
+                grpc.and_then(|grpc| grpc.get_str("authority")),
+            );
+            if grpc.and_then(|grpc| grpc.get_bool("multiMode")) == Some(true) {
+                query.append_pair("mode", "multi");
+            }
+        }
+        "httpupgrade" => {
+            let upgrade = stream.get_map("httpupgradeSettings");
+            query.append_pair("type", "httpupgrade");
+            append_opt(&mut query, "path", upgrade.and_then(|u| u.get_str("path")));
+            append_opt(&mut query, "host", upgrade.and_then(|u| u.get_str("host")));
+        }
+        "xhttp" | "splithttp" => {
+            let xhttp = stream
+                .get_map("xhttpSettings")
+                .or_else(|| stream.get_map("splithttpSettings"));
+            query.append_pair("type", "xhttp");
+            append_opt(&mut query, "path", xhttp.and_then(|x| x.get_str("path")));
+            append_opt(&mut query, "host", xhttp.and_then(|x| x.get_str("host")));
+            append_opt(&mut query, "mode", xhttp.and_then(|x| x.get_str("mode")));
+        }
+        "tcp" | "raw" => {
+            let header = stream
+                .get_map("tcpSettings")
+                .or_else(|| stream.get_map("rawSettings"))
+                .and_then(|tcp| tcp.get_map("header"))
+                .filter(|header| header.get_str("type").as_deref() == Some("http"));
+
+            query.append_pair("type", "tcp");
+            if let Some(header) = header {
+                let request = header.get_map("request");
+                query.append_pair("headerType", "http");
+                append_opt(
+                    &mut query,
+                    "path",
+                    request.and_then(|r| r.get_list("path")),
+                );
+                append_opt(&mut query, "host", request.and_then(Fields::header_host));
+            }
+        }
+        other => {
+            query.append_pair("type", other);
+        }
+    }
+
+    let security = stream.get_str("security").unwrap_or_else(|| "none".to_string());
+    query.append_pair("security", &security);
+
+    match security.as_str() {
+        "tls" => {
+            let tls = stream.get_map("tlsSettings");
+            append_opt(
+                &mut query,
+                "sni",
+                tls.and_then(|t| t.get_str("serverName")),
+            );
+            append_opt(&mut query, "alpn", tls.and_then(|t| t.get_list("alpn")));
+            append_opt(
+                &mut query,
+                "fp",
+                tls.and_then(|t| t.get_str("fingerprint")),
+            );
+            if tls.and_then(|t| t.get_bool("allowInsecure")) == Some(true) {
+                query.append_pair("allowInsecure", "1");
+            }
+        }
+        "reality" => {
+            let reality = stream.get_map("realitySettings");
+            append_opt(
+                &mut query,
+                "sni",
+                reality.and_then(|r| r.get_str("serverName")),
+            );
+            append_opt(
+                &mut query,
+                "fp",
+                reality.and_then(|r| r.get_str("fingerprint")),
+            );
+            append_opt(
+                &mut query,
+                "pbk",
+                reality.and_then(|r| r.get_str("publicKey")),
+            );
+            append_opt(
+                &mut query,
+                "sid",
+                reality.and_then(|r| r.get_str("shortId")),
+            );
+            append_opt(
+                &mut query,
+                "spx",
+                reality.and_then(|r| r.get_str("spiderX")),
+            );
+        }
+        _ => {}
+    }
+}
+
+fn sing_box_to_share_uri(outbound: &JsonObject) -> Result<String, ParseError> {
+    let protocol = outbound.require("type")?;
+    let scheme = match protocol.as_str() {
+        "vless" | "vmess" | "trojan" | "hysteria2" | "tuic" => protocol.as_str(),
+        "shadowsocks" => "ss",
+        other => return Err(ParseError::UnknownScheme(other.to_string())),
+    };
+
+    let mut url = server_url(
+        scheme,
+        &outbound.require("server")?,
+        &outbound.require("server_port")?,
+    )?;
+
+    let tls = outbound.get_map("tls").filter(|tls| tls.get_bool("enabled").unwrap_or(false));
+
+    match scheme {
+        "vless" | "vmess" => {
+            set_user_info(&mut url, &outbound.require("uuid")?, None)?;
+
+            let mut query = url.query_pairs_mut();
+            if scheme == "vless" {
+                append_opt(&mut query, "flow", outbound.get_str("flow"));
+            } else {
+                append_opt(&mut query, "aid", outbound.get_str("alter_id"));
+                append_opt(&mut query, "encryption", outbound.get_str("security"));
+            }
+            drop(query);
+
+            append_sing_box_stream(&mut url, outbound, tls);
+        }
+        "trojan" => {
+            set_user_info(&mut url, &outbound.require("password")?, None)?;
+            append_sing_box_stream(&mut url, outbound, tls);
+        }
+        "ss" => {
+            set_user_info(
+                &mut url,
+                &outbound.require("method")?,
+                Some(&outbound.require("password")?),
+            )?;
+
+            if let Some(plugin) = outbound.get_str("plugin") {
+                let plugin = match outbound.get_str("plugin_opts") {
+                    Some(opts) => format!("{};{}", plugin, opts),
+                    None => plugin,
+                };
+                url.set_path("/");
+                url.query_pairs_mut().append_pair("plugin", &plugin);
+            }
+        }
+        "hysteria2" => {
+            set_user_info(&mut url, &outbound.require("password")?, None)?;
+
+            let obfs = outbound.get_map("obfs");
+            let mut query = url.query_pairs_mut();
+            append_opt(
+                &mut query,
+                "sni",
+                tls.and_then(|t| t.get_str("server_name")),
+            );
+            if tls.and_then(|t| t.get_bool("insecure")) == Some(true) {
+                query.append_pair("insecure", "1");
+            }
+            append_opt(&mut query, "obfs", obfs.and_then(|o| o.get_str("type")));
+            append_opt(
+                &mut query,
+                "obfs-password",
+                obfs.and_then(|o| o.get_str("password")),
+            );
+            append_opt(&mut query, "up", outbound.get_str("up_mbps"));
+            append_opt(&mut query, "down", outbound.get_str("down_mbps"));
+            append_opt(&mut query, "alpn", tls.and_then(|t| t.get_list("alpn")));
+        }
+        _ => {
+            set_user_info(
+                &mut url,
+                &outbound.require("uuid")?,
+                Some(&outbound.require("password")?),
+            )?;
+
+            let mut query = url.query_pairs_mut();
+            append_opt(
+                &mut query,
+                "congestion_control",
+                outbound.get_str("congestion_control"),
+            );
+            append_opt(
+                &mut query,
+                "udp_relay_mode",
+                outbound.get_str("udp_relay_mode"),
+            );
+            append_opt(&mut query, "alpn", tls.and_then(|t| t.get_list("alpn")));
+            append_opt(
+                &mut query,
+                "sni",
+                tls.and_then(|t| t.get_str("server_name")),
+            );
+            if tls.and_then(|t| t.get_bool("insecure")) == Some(true) {
+                query.append_pair("allow_insecure", "1");
+            }
+        }
+    }
+
+    Ok(finish(url, outbound.get_str("tag")))
+}
+
+fn append_sing_box_stream(url: &mut Url, outbound: &JsonObject, tls: Option<&JsonObject>) {
+    let transport = outbound.get_map("transport");
+    let mut query = url.query_pairs_mut();
+
+    match transport.and_then(|t| t.get_str("type")).as_deref() {
+        None => {
+            query.append_pair("type", "tcp");
+        }
+        Some("ws") => {
+            query.append_pair("type", "ws");
+            append_opt(
+                &mut query,
+                "path",
+                transport.and_then(|t| t.get_str("path")),
+            );
+            append_opt(&mut query, "host", transport.and_then(Fields::header_host));
+        }
+        Some("grpc") => {
+            query.append_pair("type", "grpc");
+            append_opt(
+                &mut query,
+                "serviceName",
+                transport.and_then(|t| t.get_str("service_name")),
+            );
+        }
+        Some("httpupgrade") => {
+            query.append_pair("type", "httpupgrade");
+            append_opt(
+                &mut query,
+                "path",
+                transport.and_then(|t| t.get_str("path")),
+            );
+            append_opt(
+                &mut query,
+                "host",
+                transport.and_then(|t| t.get_str("host")),
+            );
+        }
+        // sing-box `http` is h2 unless tls is off, neither maps onto a supported transport
+        Some(other) => {
+            query.append_pair("type", other);
+        }
+    }
+
+    let reality = tls
+        .and_then(|t| t.get_map("reality"))
+        .filter(|r| r.get_bool("enabled").unwrap_or(false));
+
+    match (tls, reality) {
+        (Some(tls), Some(reality)) => {
+            query.append_pair("security", "reality");
+            append_opt(&mut query, "sni", tls.get_str("server_name"));
+            append_opt(&mut query, "fp", utls_fingerprint(tls));
+            append_opt(&mut query, "pbk", reality.get_str("public_key"));
+            append_opt(&mut query, "sid", reality.get_str("short_id"));
+        }
+        (Some(tls), None) => {
+            query.append_pair("security", "tls");
+            append_opt(&mut query, "sni", tls.get_str("server_name"));
+            append_opt(&mut query, "alpn", tls.get_list("alpn"));
+            append_opt(&mut query, "fp", utls_fingerprint(tls));
+            if tls.get_bool("insecure") == Some(true) {
+                query.append_pair("allowInsecure", "1");
+            }
+        }
+        (None, _) => {
+            query.append_pair("security", "none");
+        }
+    }
+}
+
+fn utls_fingerprint(tls: &JsonObject) -> Option<String> {
+    tls.get_map("utls")
+        .filter(|utls| utls.get_bool("enabled").unwrap_or(false))
+        .and_then(|utls| utls.get_str("fingerprint"))
+}
+
+fn first_of<'a>(settings: &'a JsonObject, key: &str) -> Result<&'a JsonObject, ParseError> {
+    get_array(settings, key)
+        .and_then(|servers| servers.first())
+        .and_then(JsonValue::as_object)
+        .ok_or(ParseError::FieldMissing(key.to_string()))
+}
+
+fn get_array<'a>(object: &'a JsonObject, key: &str) -> Option<&'a Vec<JsonValue>> {
+    object.get(key).and_then(JsonValue::as_array)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(json: &str) -> ParseReport {
+        work(json).unwrap().assert_all_accepted()
+    }
+
+    #[test]
+    fn converts_xray_outbounds() {
+        let report = convert(
+            r#"{ "outbounds": [
+  { "protocol": "vless", "tag": "reality",
+    "settings": { "vnext": [{ "address": "24.120.32.42", "port": 443,
+                              "users": [{ "id": "uuid", "flow": "xtls-rprx-vision" }] }] },
+    "streamSettings": { "network": "grpc", "security": "reality",
+                        "realitySettings": { "serverName": "unpkg.com", "publicKey": "key",
+                                             "fingerprint": "chrome" },
+                        "grpcSettings": { "serviceName": "grpc", "multiMode": true } } },
+  { "protocol": "vmess", "tag": "ws",
+    "settings": { "vnext": [{ "address": "a.com", "port": 443,
+                              "users": [{ "id": "uuid", "alterId": 0 }] }] },
+    "streamSettings": { "network": "ws", "security": "tls",
+                        "tlsSettings": { "serverName": "s.com", "allowInsecure": true },
+                        "wsSettings": { "path": "/ws", "headers": { "Host": "cdn.com" } } } },
+  { "protocol": "trojan", "tag": "http",
+    "settings": { "servers": [{ "address": "b.com", "port": 80, "password": "pw" }] },
+    "streamSettings": { "network": "raw", "rawSettings": { "header": { "type": "http",
+                        "request": { "path": ["/a"], "headers": { "Host": ["h.com"] } } } } } },
+  { "protocol": "shadowsocks", "tag": "ss",
+    "settings": { "servers": [{ "address": "1.2.3.4", "port": 8388,
+                                "method": "aes-128-gcm", "password": "pw" }] } },
+  { "protocol": "freedom", "tag": "direct" },
+  { "protocol": "blackhole", "tag": "block" }
+] }"#,
+        );
+
+        assert_eq!(report.accepted_count(), 4);
+
+        let reality = &report.configs[0];
+        assert_eq!(reality.name(), Some("reality"));
+        assert_eq!(reality.user().unwrap().flow.as_deref(), Some("xtls-rprx-vision"));
+        assert_eq!((reality.network(), reality.path()), (Some("grpc"), Some("grpc")));
+        let settings = reality.reality_settings().unwrap();
+        assert_eq!((settings.server_name.as_str(), settings.short_id.as_str()), ("unpkg.com", ""));
+
+        let ws = &report.configs[1];
+        assert_eq!((ws.path(), ws.host()), (Some("/ws"), Some("cdn.com")));
+        assert!(ws.tls_settings().unwrap().allow_insecure);
+
+        let http = &report.configs[2];
+        assert_eq!(
+            (http.network(), http.path(), http.host()),
+            (Some("tcp"), Some("/a"), Some("h.com"))
+        );
+        assert_eq!(http.security(), None);
+
+        assert_eq!(report.configs[3].method(), Some("aes-128-gcm"));
+    }
+
+    #[test]
+    fn converts_sing_box_outbounds() {
+        let report = convert(
+            r#"{ "outbounds": [
+  { "type": "vless", "tag": "reality", "server": "24.120.32.42", "server_port": 443,
+    "uuid": "uuid", "flow": "xtls-rprx-vision",
+    "tls": { "enabled": true, "server_name": "unpkg.com",
+             "utls": { "enabled": true, "fingerprint": "firefox" },
+             "reality": { "enabled": true, "public_key": "key", "short_id": "ab" } },
+    "transport": { "type": "httpupgrade", "path": "/up", "host": "up.com" } },
+  { "type": "hysteria2", "tag": "hy", "server": "h.com", "server_port": 443, "password": "pw",
+    "up_mbps": 50, "down_mbps": 100, "obfs": { "type": "salamander", "password": "x" },
+    "tls": { "enabled": true, "server_name": "s.com", "insecure": true, "alpn": ["h3"] } },
+  { "type": "tuic", "tag": "tuic", "server": "t.com", "server_port": 443, "uuid": "uuid",
+    "password": "pw", "congestion_control": "bbr", "udp_relay_mode": "native",
+    "tls": { "enabled": true, "server_name": "t.com" } },
+  { "type": "shadowsocks", "tag": "ss", "server": "1.2.3.4", "server_port": 8388,
+    "method": "chacha20-ietf-poly1305", "password": "pw",
+    "plugin": "obfs-local", "plugin_opts": "obfs=http;obfs-host=example.com" },
+  { "type": "direct", "tag": "direct" },
+  { "type": "selector", "tag": "select", "outbounds": ["reality"] }
+] }"#,
+        );
+
+        assert_eq!(report.accepted_count(), 4);
+
+        let reality = &report.configs[0];
+        assert_eq!((reality.network(), reality.host()), (Some("httpupgrade"), Some("up.com")));
+        let settings = reality.reality_settings().unwrap();
+        assert_eq!(settings.short_id, "ab");
+        assert_eq!(settings.fingerprint.as_deref(), Some("firefox"));
+
+        let hysteria2 = &report.configs[1];
+        assert!(hysteria2.insecure());
+        assert_eq!((hysteria2.obfs(), hysteria2.obfs_password()), (Some("salamander"), Some("x")));
+        assert_eq!((hysteria2.up_mbps(), hysteria2.down_mbps()), (Some(50), Some(100)));
+        assert_eq!(hysteria2.alpn(), ["h3"]);
+
+        let tuic = &report.configs[2];
+        assert_eq!((tuic.congestion_control(), tuic.sni()), (Some("bbr"), Some("t.com")));
+
+        let ss = &report.configs[3];
+        assert_eq!(
+            (ss.plugin(), ss.plugin_opts()),
+            (Some("obfs-local"), Some("obfs=http;obfs-host=example.com"))
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_and_incomplete_outbounds() {
+        let report = work(
+            r#"{ "outbounds": [
+  { "type": "trojan", "tag": "ok", "server": "a.com", "server_port": 443, "password": "pw" },
+  { "protocol": "socks", "tag": "socks", "settings": {} },
+  { "type": "vless", "tag": "no-uuid", "server": "b.com", "server_port": 443 },
+  "not an object",
+  { "tag": "nothing" }
+] }"#,
+        )
+        .unwrap();
+
+        assert_eq!(report.accepted_count(), 1);
+        assert_eq!(
+            report.rejections(),
+            vec![
+                (2, ParseError::UnknownScheme("socks".to_string())),
+                (3, ParseError::FieldMissing("uuid".to_string())),
+                (
+                    4,
+                    ParseError::UnknownFieldType {
+                        current: "outbound".to_string(),
+                        expected: "object".to_string(),
+                    }
+                ),
+                (5, ParseError::FieldMissing("protocol".to_string())),
+            ]
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+
+use crate::common::parsers::{
+    fields::server_url,
+    proxy_config::{self, ParseError, ParseReport, append_opt, set_user_info},
+};
+
+// example sip008 document
+// {
+//   "version": 1,
+//   "servers": [
+//     {
+//       "id": "27b8a625-4f4b-4428-9f0f-8a2317db7c79",
+//       "remarks": "Name of the server",
+//       "server": "example.com",
+//       "server_port": 8388,
+//       "password": "example",
+//       "method": "chacha20-ietf-poly1305",
+//       "plugin": "xxx",
+//       "plugin_opts": "xxxxx"
+//     }
+//   ],
+//   "bytes_used": 274877906944,
+//   "bytes_remaining": 824633720832
+// }
+
+/// Traffic counters a SIP008 provider may attach to the document.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes_used: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes_remaining: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Document {
+    servers: Vec<JsonValue>,
+    #[serde(default)]
+    bytes_used: Option<u64>,
+    #[serde(default)]
+    bytes_remaining: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Server {
+    #[serde(default)]
+    remarks: Option<String>,
+    server: Option<String>,
+    server_port: Option<u16>,
+    password: Option<String>,
+    method: Option<String>,
+    #[serde(default)]
+    plugin: Option<String>,
+    #[serde(default)]
+    plugin_opts: Option<String>,
+}
+
+/// SIP008 documents are a JSON object with a `version` and a `servers` list.
+pub fn is_sip008_config(payload: &str) -> bool {
+    let payload = payload.trim();
+    if !payload.starts_with('{') {
+        return false;
+    }
+
+    serde_json::from_str::<Map<String, JsonValue>>(payload)
+        .map(|document| {
+            document.contains_key("version")
+                && document.get("servers").is_some_and(JsonValue::is_array)
+        })
+        .unwrap_or(false)
+}
+
+/// Converts every entry of `servers`, each one is reported by its position in the list.
+pub fn work(payload: &str) -> Result<ParseReport, serde_json::Error> {
+    let document: Document = serde_json::from_str(payload.trim())?;
+    let mut report = ParseReport::default();
+
+    for (index, server) in document.servers.into_iter().enumerate() {
+        let server = match serde_json::from_value::<Server>(server) {
+            Ok(server) => server,
+            Err(e) => {
+                report.reject(
+                    index + 1,
+                    Some("ss".to_string()),
+                    String::from("<invalid server>"),
+                    ParseError::MalformedEntry(e.to_string()),
+                );
+                continue;
+            }
+        };
+
+        match to_share_uri(&server) {
+            Ok(uri) => report.push_line(index + 1, &uri),
+            Err(reason) => report.reject(
+                index + 1,
+                Some("ss".to_string()),
+                format!("ss: {}", server.remarks.unwrap_or_default()),
+                reason,
+            ),
+        }
+    }
+
+    if document.bytes_used.is_some() || document.bytes_remaining.is_some() {
+        report.usage = Some(Usage {
+            bytes_used: document.bytes_used,
+            bytes_remaining: document.bytes_remaining,
+        });
+    }
+
+    Ok(report)
+}
+
+fn to_share_uri(server: &Server) -> Result<String, ParseError> {
+    let address = require(&server.server, "server")?;
+    let port = server
+        .server_port
+        .ok_or(ParseError::FieldMissing("server_port".to_string()))?;
+
+    let mut url = server_url("ss", address, port)?;
+
+    set_user_info(
+        &mut url,
+        require(&server.method, "method")?,
+        Some(require(&server.password, "password")?),
+    )?;
+
+    if let Some(plugin) = server.plugin.as_deref().filter(|plugin| !plugin.is_empty()) {
+        let plugin = match server
+            .plugin_opts
+            .as_deref()
+            .filter(|opts| !opts.is_empty())
+        {
+            Some(opts) => format!("{};{}", plugin, opts),
+            None => plugin.to_string(),
+        };
+
+        url.set_path("/");
+        append_opt(&mut url.query_pairs_mut(), "plugin", Some(plugin));
+    }
+
+    if let Some(remarks) = server.remarks.as_deref().filter(|name| !name.is_empty()) {
+        url.set_fragment(Some(&proxy_config::encode_component(remarks)));
+    }
+
+    Ok(url.to_string())
+}
+
+fn require<'a>(value: &'a Option<String>, key: &str) -> Result<&'a str, ParseError> {
+    value
+        .as_deref()
+        .filter(|value| !value.is_empty())
+        .ok_or(ParseError::FieldMissing(key.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_servers_and_usage() {
+        let report = work(
+            r#"{
+  "version": 1,
+  "servers": [
+    { "id": "1", "remarks": "Имя сервера", "server": "example.com", "server_port": 8388,
+      "password": "pass:word", "method": "chacha20-ietf-poly1305",
+      "plugin": "obfs-local", "plugin_opts": "obfs=http;obfs-host=example.com" },
+    { "id": "2", "server": "2001:db8::1", "server_port": 443, "password": "pw",
+      "method": "aes-256-gcm", "plugin": "", "plugin_opts": "" }
+  ],
+  "bytes_used": 274877906944,
+  "bytes_remaining": 824633720832
+}"#,
+        )
+        .unwrap()
+        .assert_all_accepted();
+
+        let obfs = &report.configs[0];
+        assert_eq!(obfs.name(), Some("Имя сервера"));
+        assert_eq!((obfs.address(), obfs.port()), ("example.com", 8388));
+        assert_eq!(obfs.method(), Some("chacha20-ietf-poly1305"));
+        assert_eq!(obfs.password(), Some("pass:word"));
+        assert_eq!(
+            (obfs.plugin(), obfs.plugin_opts()),
+            (Some("obfs-local"), Some("obfs=http;obfs-host=example.com"))
+        );
+
+        let plain = &report.configs[1];
+        assert_eq!((plain.port(), plain.name()), (443, None));
+        assert_eq!(plain.plugin(), None);
+
+        assert_eq!(
+            report.usage,
+            Some(Usage {
+                bytes_used: Some(274877906944),
+                bytes_remaining: Some(824633720832),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_incomplete_servers() {
+        let report = work(
+            r#"{
+  "version": 1,
+  "servers": [
+    { "server": "a.com", "server_port": 8388, "password": "pw", "method": "aes-128-gcm" },
+    { "remarks": "no port", "server": "b.com", "password": "pw", "method": "aes-128-gcm" },
+    { "server": "c.com", "server_port": 8388, "password": "", "method": "aes-128-gcm" },
+    { "server": "d.com", "server_port": "not a port" }
+  ]
+}"#,
+        )
+        .unwrap();
+
+        assert_eq!(report.accepted_count(), 1);
+        assert_eq!(report.usage, None);
+
+        let rejected = report.rejections();
+        assert_eq!(rejected[0], (2, ParseError::FieldMissing("server_port".to_string())));
+        assert_eq!(rejected[1], (3, ParseError::FieldMissing("password".to_string())));
+        assert_eq!(rejected[2].0, 4);
+        assert!(matches!(rejected[2].1, ParseError::MalformedEntry(_)));
+    }
+}
@@ -1,36 +1,282 @@
-use crate::services::{ ConfigService, StatusService};
-use luxnulla::{CommandRequest, CommandResponse, OkCommandResponse};
-use std::path::PathBuf;
+use crate::services::{
+    ConfigService, GroupService, SelectError, Selected, Selector, Settings, SettingsWatcher,
+    StatusService, StorageService,
+    backend::{ApplyError, BackendError, ConfigStore, ConfigWatcher, CoreSupervisor},
+    subscriptions::spawn_refresher,
+    xray::logs::{LOG_BUFFER_LINES, LogBuffer},
+};
+use luxnulla::{
+    CommandRequest, CommandResponse, ConfigFile, CoreState, EditableConfig, ErrorCommandResponse,
+    OkCommandResponse, XRAY_LOG_FILE,
+};
+use std::{fs, io, path::PathBuf, sync::Arc};
+use tokio::sync::{broadcast, watch};
+
+const NOTIFICATION_CAPACITY: usize = 16;
 
 pub struct CommandHandler {
+    settings: watch::Receiver<Settings>,
     status_service: StatusService,
+    group_service: GroupService,
     config_service: ConfigService,
+    config_store: Arc<ConfigStore>,
+    core_supervisor: Arc<CoreSupervisor>,
+    selector: Arc<Selector>,
+    settings_watcher: Arc<SettingsWatcher>,
+    notifications: broadcast::Sender<CommandResponse>,
 }
 
 impl CommandHandler {
-    pub fn new(config_dir: PathBuf) -> Self {
+    pub fn new(config_dir: PathBuf, storage: Arc<StorageService>, settings: Settings) -> Self {
+        let logs = Arc::new(LogBuffer::new(
+            LOG_BUFFER_LINES,
+            Some(config_dir.join(XRAY_LOG_FILE)),
+        ));
+        let config_service = ConfigService::new(config_dir.clone());
+
+        let profile = &settings.profile;
+        println!("Using profile '{}' with {}", profile.name, profile.core);
+
+        let config_store = Arc::new(ConfigStore::new(
+            profile.backend(),
+            settings.core.clone(),
+            &config_dir,
+        ));
+        let core_supervisor = Arc::new(CoreSupervisor::new(
+            config_store.backend(),
+            config_store.config_path().to_path_buf(),
+            logs,
+        ));
+
+        let (notifications, _) = broadcast::channel(NOTIFICATION_CAPACITY);
+
+        let config_watcher = Arc::new(ConfigWatcher::new(
+            config_store.clone(),
+            core_supervisor.clone(),
+            notifications.clone(),
+        ));
+        if let Err(e) = config_watcher.clone().watch() {
+            eprintln!(
+                "Warning: Could not watch {:?}, edits need a manual restart: {}",
+                config_store.config_path(),
+                e
+            );
+        }
+
+        let status_service = StatusService::new(
+            config_dir.clone(),
+            storage.clone(),
+            core_supervisor.clone(),
+            settings.http_bind,
+        );
+
+        let group_service = GroupService::new(config_dir.clone(), storage.clone());
+
+        let selector = Arc::new(Selector::new(
+            config_dir.clone(),
+            storage.clone(),
+            config_store.clone(),
+            core_supervisor.clone(),
+        ));
+
+        let settings_watcher = Arc::new(SettingsWatcher::new(
+            config_dir,
+            settings,
+            config_store.clone(),
+            selector.clone(),
+            notifications.clone(),
+        ));
+        let settings = settings_watcher.settings();
+        if let Err(e) = settings_watcher.clone().watch() {
+            eprintln!(
+                "Warning: Could not watch settings, edits need a daemon restart: {}",
+                e
+            );
+        }
+
+        spawn_refresher(storage, settings.clone());
+
         Self {
-            status_service: StatusService::new(),
-            config_service: ConfigService::new(config_dir),
+            settings,
+            status_service,
+            group_service,
+            config_service,
+            config_store,
+            core_supervisor,
+            selector,
+            settings_watcher,
+            notifications,
         }
     }
 
+    pub fn xray_logs(&self) -> Arc<LogBuffer> {
+        self.core_supervisor.logs()
+    }
+
+    pub fn config_store(&self) -> Arc<ConfigStore> {
+        self.config_store.clone()
+    }
+
+    pub fn selector(&self) -> Arc<Selector> {
+        self.selector.clone()
+    }
+
+    /// The running settings, updated as `luxnulla.kdl` is reloaded.
+    pub fn settings(&self) -> watch::Receiver<Settings> {
+        self.settings.clone()
+    }
+
+    /// Outcome of every reload of an edited config or settings file.
+    pub fn notifications(&self) -> broadcast::Receiver<CommandResponse> {
+        self.notifications.subscribe()
+    }
+
     pub async fn handle_command(&self, request: CommandRequest) -> CommandResponse {
         match request {
             CommandRequest::Status => self.status_service.get_status(),
 
-            CommandRequest::Restart => {
-                CommandResponse::Ok(OkCommandResponse::Message(String::from(
-                    "xray is restarted TODO",
-                )))
-                // self.subscription_service
-                // .refresh_default_subscription()
-                // .await
+            CommandRequest::Start => self.core_supervisor.start().await,
+
+            CommandRequest::Stop => self.core_supervisor.stop().await,
+
+            CommandRequest::Restart => self.core_supervisor.restart().await,
+
+            CommandRequest::Rollback { steps } => self.rollback(steps).await,
+
+            // following needs the socket, `ClientHandler` streams it and only the tail ends up here
+            CommandRequest::Logs { lines, .. } => CommandResponse::Ok(OkCommandResponse::Logs(
+                self.core_supervisor.logs().tail(lines),
+            )),
+
+            CommandRequest::ReadConfig { file } => self.read_config(file),
+
+            CommandRequest::WriteConfig { file, content } => match file {
+                ConfigFile::Core => self.write_core_config(&content).await,
+                ConfigFile::Luxnulla => self.settings_watcher.save(&content).await,
+            },
+
+            CommandRequest::ListGroups => self.group_service.list(),
+
+            CommandRequest::ShowGroup { name } => self.group_service.show(&name),
+
+            CommandRequest::AddGroup { name, payload } => {
+                self.group_service.add(&name, &payload).await
+            }
+
+            CommandRequest::RemoveGroup { name } => self.group_service.remove(&name),
+
+            CommandRequest::RenameGroup { name, new_name } => {
+                self.group_service.rename(&name, &new_name)
+            }
+
+            CommandRequest::RefreshGroup { name } => self.group_service.refresh(&name).await,
+
+            CommandRequest::Select { group, server } => {
+                selected(self.selector.select(&group, server.as_ref()).await)
+            }
+
+            CommandRequest::SelectNext => selected(self.selector.step(true).await),
+
+            CommandRequest::SelectPrevious => selected(self.selector.step(false).await),
+        }
+    }
+
+    /// Content of a file for the client to edit, a file that does not exist yet is empty.
+    fn read_config(&self, file: ConfigFile) -> CommandResponse {
+        let path = match file {
+            ConfigFile::Core => self.config_store.config_path().to_path_buf(),
+            ConfigFile::Luxnulla => match self.config_service.get_luxnulla_config_path() {
+                Ok(path) => PathBuf::from(path),
+                Err(e) => return CommandResponse::Err(ErrorCommandResponse::Message(e)),
+            },
+        };
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return CommandResponse::Err(ErrorCommandResponse::Message(format!(
+                    "Failed to read {:?}: {}",
+                    path, e
+                )));
+            }
+        };
+
+        CommandResponse::Ok(OkCommandResponse::Config(EditableConfig {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            content,
+            editor: self.settings.borrow().editor.clone(),
+        }))
+    }
+
+    /// Replaces the core config with an edit the core accepts and restarts the core
+    /// when it is running.
+    async fn write_core_config(&self, content: &str) -> CommandResponse {
+        let file = self.config_store.backend().config_file().to_string();
+
+        match self.config_store.save(content).await {
+            Ok(()) => self.restart_if_running(format!("{} was saved", file)).await,
+            Err(e @ ApplyError::Json(_))
+            | Err(e @ ApplyError::Backend(BackendError::InvalidConfig { .. })) => {
+                CommandResponse::Err(ErrorCommandResponse::InvalidConfig(e.to_string()))
+            }
+            Err(e) => CommandResponse::Err(ErrorCommandResponse::Message(format!(
+                "Failed to save {}: {}",
+                file, e
+            ))),
+        }
+    }
+
+    /// Restores a previous config and restarts the core when it is running, so it takes effect.
+    async fn rollback(&self, steps: usize) -> CommandResponse {
+        let restored = match self.config_store.rollback(steps).await {
+            Ok(restored) => restored,
+            Err(e) => {
+                return CommandResponse::Err(ErrorCommandResponse::Message(format!(
+                    "Failed to roll back: {}",
+                    e
+                )));
             }
+        };
 
-            CommandRequest::EditXray => self.config_service.edit_xray_config().await,
+        let message = format!(
+            "Rolled back to {}",
+            restored
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        );
 
-            CommandRequest::EditLuxnulla => self.config_service.edit_luxnulla_config().await
+        self.restart_if_running(message).await
+    }
+
+    /// Restarts the core so a replaced config takes effect, `message` says what was replaced.
+    async fn restart_if_running(&self, message: String) -> CommandResponse {
+        if self.core_supervisor.status().state == CoreState::Stopped {
+            return CommandResponse::Ok(OkCommandResponse::Message(message));
+        }
+
+        match self.core_supervisor.restart().await {
+            CommandResponse::Ok(OkCommandResponse::Message(restarted)) => CommandResponse::Ok(
+                OkCommandResponse::Message(format!("{}, {}", message, restarted)),
+            ),
+            CommandResponse::Err(ErrorCommandResponse::Message(e)) => CommandResponse::Err(
+                ErrorCommandResponse::Message(format!("{}, but {}", message, e)),
+            ),
+            response => response,
         }
     }
 }
+
+fn selected(result: Result<Selected, SelectError>) -> CommandResponse {
+    match result {
+        Ok(selected) => selected.into_response(),
+        Err(e) => CommandResponse::Err(ErrorCommandResponse::Message(format!(
+            "Failed to select a server: {}",
+            e
+        ))),
+    }
+}
@@ -0,0 +1,69 @@
+use crate::services::{SelectError, Selector};
+use axum::{Extension, Json, http::StatusCode, response::IntoResponse};
+use luxnulla::{CommandResponse, ErrorCommandResponse, OkCommandResponse, ServerRef};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct SelectServer {
+    group: String,
+    /// Index or name of the server, the first one when missing.
+    #[serde(default)]
+    server: Option<ServerRef>,
+}
+
+#[axum::debug_handler]
+pub async fn get_active(Extension(selector): Extension<Arc<Selector>>) -> impl IntoResponse {
+    Json(json!({ "active": selector.active() }))
+}
+
+/// Selects a server like `luxnulla use`: the selection is saved, the core config is
+/// regenerated and the core is restarted.
+#[axum::debug_handler]
+pub async fn select_active(
+    Extension(selector): Extension<Arc<Selector>>,
+    Json(req): Json<SelectServer>,
+) -> impl IntoResponse {
+    let selected = match selector.select(&req.group, req.server.as_ref()).await {
+        Ok(selected) => selected,
+        Err(e) => {
+            let status = match e {
+                SelectError::GroupNotFound(_) | SelectError::ServerNotFound { .. } => {
+                    StatusCode::NOT_FOUND
+                }
+                SelectError::Storage(_) | SelectError::Save(_) => {
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+                _ => StatusCode::UNPROCESSABLE_ENTITY,
+            };
+
+            return (
+                status,
+                Json(json!({
+                    "error": "Failed to select server",
+                    "details": e.to_string()
+                })),
+            )
+                .into_response();
+        }
+    };
+
+    match selected.core {
+        CommandResponse::Err(ErrorCommandResponse::Message(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "error": "Server was selected, but the core was not restarted",
+                "details": e,
+                "active": selected.active
+            })),
+        )
+            .into_response(),
+        CommandResponse::Ok(OkCommandResponse::Message(core)) => (
+            StatusCode::OK,
+            Json(json!({ "active": selected.active, "core": core })),
+        )
+            .into_response(),
+        _ => (StatusCode::OK, Json(json!({ "active": selected.active }))).into_response(),
+    }
+}
@@ -1,89 +1,16 @@
 use crate::{
-    common::parsers::proxy_config::{self, ProxyConfig},
-    services::{Group, StorageService, xray::fetcher::get_configs},
+    common::parsers::{proxy_config::RejectedLine, sip008::Usage},
+    http::services::config_builder::select_config,
+    services::{
+        Group, StorageError, StorageService, backend::ConfigStore,
+        xray::fetcher::{ConfigType, determine_config_type, process_config},
+    },
 };
-use axum::{extract::{Path, State}, http::StatusCode, response::IntoResponse, Json};
-use base64::{Engine, prelude::BASE64_STANDARD};
+use axum::{extract::{Path, Query, State}, http::StatusCode, response::IntoResponse, Extension, Json};
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use std::sync::Arc;
-use url::Url;
-
-pub enum ConfigType {
-    RAW,
-    BASE64,
-    URL,
-}
-
-fn determine_config_type(config: &str) -> Result<ConfigType, std::io::Error> {
-    if proxy_config::is_supported_scheme(config) {
-        Ok(ConfigType::RAW)
-    } else if config.trim().starts_with("http") || config.trim().starts_with("https") {
-        Ok(ConfigType::URL)
-    } else if BASE64_STANDARD.decode(config.trim()).is_ok() {
-        Ok(ConfigType::BASE64)
-    } else {
-        Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            "Invalid config",
-        ))
-    }
-}
-
-async fn process_config(payload: &str) -> Result<Vec<ProxyConfig>, std::io::Error> {
-    match determine_config_type(payload)? {
-        ConfigType::RAW => {
-            if let Ok(_) = Url::parse(&payload) {
-                if let Ok(work_result) = proxy_config::work(&payload) {
-                    Ok(work_result)
-                } else {
-                    Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        "Failed to perform work with config",
-                    ))
-                }
-            } else {
-                Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    "Invalid URL",
-                ))
-            }
-        }
-        ConfigType::BASE64 => {
-            let raw_config = proxy_config::decode_config_from_base64(payload);
-
-            if let Ok(config) = raw_config {
-                if let Ok(_) = Url::parse(&config) {
-                    if let Ok(work_result) = proxy_config::work(&config) {
-                        Ok(work_result)
-                    } else {
-                        Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            "Failed to perform work with config",
-                        ))
-                    }
-                } else {
-                    Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidInput,
-                        "Invalid URL",
-                    ))
-                }
-            } else {
-                Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    "Invalid config",
-                ))
-            }
-        }
-        ConfigType::URL => match get_configs(payload).await {
-            Ok(configs) => Ok(configs),
-            Err(_) => Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to fetch configs",
-            )),
-        },
-    }
-}
 
 #[derive(Deserialize)]
 pub struct CreateGroup {
@@ -91,10 +18,13 @@ pub struct CreateGroup {
     payload: String,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Serialize)]
 pub struct CreateGroupResponse {
     name: String,
     configs: Value,
+    rejected: Vec<RejectedLine>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<Usage>,
 }
 
 #[axum::debug_handler]
@@ -102,8 +32,8 @@ pub async fn create_group(
     State(storage): State<Arc<StorageService>>,
     Json(req): Json<CreateGroup>,
 ) -> impl IntoResponse {
-    let decoded_config = match process_config(&req.payload).await {
-        Ok(config) => config,
+    let report = match process_config(&req.payload).await {
+        Ok(report) => report,
         Err(e) => {
             return (
                 StatusCode::BAD_REQUEST,
@@ -116,17 +46,32 @@ pub async fn create_group(
         }
     };
 
-    let group = Group::new(req.name.clone(), json!(decoded_config));
+    let mut group = Group::new(req.name.clone(), json!(report.configs));
+    group.usage = report.usage.clone();
+    group.refreshed_at = Some(Utc::now());
+    if let Ok(ConfigType::Url) = determine_config_type(&req.payload) {
+        group.source = Some(req.payload.trim().to_string());
+    }
 
     match storage.store_group(group) {
         Ok(()) => (
             StatusCode::CREATED,
             Json(CreateGroupResponse {
                 name: req.name,
-                configs: json!(decoded_config),
+                configs: json!(report.configs),
+                rejected: report.rejected,
+                usage: report.usage,
             }),
         )
             .into_response(),
+        Err(e @ StorageError::InvalidGroupName(_)) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": "Invalid group name",
+                "details": e.to_string()
+            })),
+        )
+            .into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({
@@ -219,3 +164,119 @@ pub async fn delete_group(
             .into_response(),
     }
 }
+
+#[axum::debug_handler]
+pub async fn export_group(
+    State(storage): State<Arc<StorageService>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let group = match storage.get_group(&name) {
+        Ok(Some(group)) => group,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "error": "Group not found",
+                    "details": name
+                })),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "error": "Failed to retrieve group",
+                    "details": e.to_string()
+                })),
+            )
+                .into_response();
+        }
+    };
+
+    match group.proxy_configs() {
+        Ok(configs) => (
+            StatusCode::OK,
+            configs
+                .iter()
+                .map(|config| config.to_share_uri())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "error": "Failed to export group",
+                "details": e.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SelectConfig {
+    #[serde(default)]
+    index: usize,
+}
+
+/// Renders the core config that routes through a server of the group. Nothing is written,
+/// `POST /active` selects the server.
+#[axum::debug_handler]
+pub async fn generate_core_config(
+    State(storage): State<Arc<StorageService>>,
+    Extension(config_store): Extension<Arc<ConfigStore>>,
+    Path(name): Path<String>,
+    Query(select): Query<SelectConfig>,
+) -> impl IntoResponse {
+    let group = match storage.get_group(&name) {
+        Ok(Some(group)) => group,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "error": "Group not found",
+                    "details": name
+                })),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "error": "Failed to retrieve group",
+                    "details": e.to_string()
+                })),
+            )
+                .into_response();
+        }
+    };
+
+    let config = match select_config(&group, select.index) {
+        Ok(config) => config,
+        Err(e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "error": "Config not found",
+                    "details": e.to_string()
+                })),
+            )
+                .into_response();
+        }
+    };
+
+    match config_store.render(&config) {
+        Ok(document) => (StatusCode::OK, Json(document)).into_response(),
+        Err(e) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({
+                "error": format!("Failed to build {} config", config_store.backend().core()),
+                "details": e.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,64 @@
+use crate::services::xray::logs::LogBuffer;
+use axum::{
+    Extension, Json,
+    extract::Query,
+    http::StatusCode,
+    response::{
+        IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures::{StreamExt, stream};
+use serde::Deserialize;
+use serde_json::json;
+use std::{convert::Infallible, sync::Arc};
+use tokio::sync::broadcast::error::RecvError;
+
+const DEFAULT_LINES: usize = 100;
+
+fn default_lines() -> usize {
+    DEFAULT_LINES
+}
+
+#[derive(Deserialize)]
+pub struct LogsQuery {
+    #[serde(default)]
+    follow: bool,
+    #[serde(default = "default_lines")]
+    lines: usize,
+}
+
+/// Returns the tail of the core log, with `follow` it becomes a server-sent event stream
+/// that keeps delivering new lines.
+#[axum::debug_handler]
+pub async fn get_logs(
+    Extension(logs): Extension<Arc<LogBuffer>>,
+    Query(query): Query<LogsQuery>,
+) -> impl IntoResponse {
+    if !query.follow {
+        return (
+            StatusCode::OK,
+            Json(json!({
+                "lines": logs.tail(query.lines)
+            })),
+        )
+            .into_response();
+    }
+
+    let (tail, receiver) = logs.follow(query.lines);
+
+    let backlog = stream::iter(tail).map(|line| Ok::<_, Infallible>(Event::default().data(line)));
+    let live = stream::unfold(receiver, |mut receiver| async move {
+        let line = match receiver.recv().await {
+            Ok(line) => line,
+            Err(RecvError::Lagged(skipped)) => format!("luxnulla: skipped {} lines", skipped),
+            Err(RecvError::Closed) => return None,
+        };
+
+        Some((Ok(Event::default().data(line)), receiver))
+    });
+
+    Sse::new(backlog.chain(live))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
@@ -1 +1,3 @@
+pub mod active;
 pub mod groups;
+pub mod logs;
@@ -1,16 +1,19 @@
-use std::sync::Arc;
-use axum::Json;
+use std::{net::SocketAddr, sync::Arc};
+use axum::{Extension, Json};
 use axum::{Router, routing::{get, post, put, delete}};
 use reqwest::Method;
 use serde_json::{Value, json};
 use tower::ServiceBuilder;
-use tower_http::cors::{Any, CorsLayer};
+use tokio::sync::watch;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 
-use crate::http::handlers::groups::{create_group, delete_group, get_groups, update_group};
+use crate::http::handlers::active::{get_active, select_active};
+use crate::http::handlers::groups::{
+    create_group, delete_group, export_group, generate_core_config, get_groups, update_group,
+};
+use crate::http::handlers::logs::get_logs;
 use crate::http::services::model::xray_config::XrayClientConfig;
-use crate::services::{self};
-
-const SOCKET: &str = "0.0.0.0:3000";
+use crate::services::{self, Selector, Settings, backend::ConfigStore, xray::logs::LogBuffer};
 
 async fn root() -> &'static str {
     return "Server is working";
@@ -21,23 +24,50 @@ async fn get_parsed_xray_configs() -> Json<Value> {
     const URL: &str =
         "https://raw.githubusercontent.com/barry-far/V2ray-Config/refs/heads/main/Sub1.txt";
 
-    let configs = services::xray::fetcher::get_configs(URL).await;
+    let report = services::xray::fetcher::get_configs(URL).await.unwrap();
+
+    let mut xray_configs = Vec::new();
+    let mut incompatible = Vec::new();
 
-    let configs = configs
-        .unwrap()
-        .iter()
-        .map(|config| XrayClientConfig::new(config))
-        .collect::<Vec<_>>();
+    for config in report.configs.iter() {
+        match XrayClientConfig::new(config) {
+            Ok(xray_config) => xray_configs.push(xray_config),
+            Err(e) => incompatible.push(json!({
+                "name": config.name(),
+                "protocol": config.protocol(),
+                "requires": config.compatible_cores(),
+                "details": e.to_string(),
+            })),
+        }
+    }
 
-    Json(json!(configs))
+    Json(json!({
+        "configs": xray_configs,
+        "incompatible": incompatible,
+        "rejected": report.rejected,
+    }))
 }
 
-pub fn init() -> tokio::task::JoinHandle<()> {
-    let storage_service_state = Arc::new(services::StorageService::new());
+pub fn init(
+    bind: SocketAddr,
+    storage_service_state: Arc<services::StorageService>,
+    logs: Arc<LogBuffer>,
+    config_store: Arc<ConfigStore>,
+    selector: Arc<Selector>,
+    settings: watch::Receiver<Settings>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        // checked on every request so reloaded origins apply without a restart
+        let allow_origin = AllowOrigin::predicate(move |origin, _| {
+            let origins = &settings.borrow().cors_origins;
+            origins.is_empty()
+                || origins
+                    .iter()
+                    .any(|allowed| allowed.as_bytes() == origin.as_bytes())
+        });
 
-    tokio::spawn(async {
         let cors_layer = CorsLayer::new()
-            .allow_origin(Any)
+            .allow_origin(allow_origin)
             .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
             .allow_headers(Any);
 
@@ -47,12 +77,19 @@ pub fn init() -> tokio::task::JoinHandle<()> {
             .route("/groups", get(get_groups))
             .route("/group", post(create_group).put(update_group))
             .route("/group/{name}", delete(delete_group))
+            .route("/group/{name}/export", get(export_group))
+            .route("/group/{name}/config", post(generate_core_config))
+            .route("/active", get(get_active).post(select_active))
+            .route("/logs", get(get_logs))
             .with_state(storage_service_state)
+            .layer(Extension(logs))
+            .layer(Extension(config_store))
+            .layer(Extension(selector))
             .layer(ServiceBuilder::new().layer(cors_layer));
 
-        let listener = tokio::net::TcpListener::bind(SOCKET).await.unwrap();
+        let listener = tokio::net::TcpListener::bind(bind).await.unwrap();
 
-        println!("http server bind on {}", SOCKET);
+        println!("http server bind on {}", bind);
 
         axum::serve(listener, app).await.unwrap();
     })
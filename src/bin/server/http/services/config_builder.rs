@@ -1,25 +1,236 @@
 use crate::{
-    common::parsers::proxy_config::ProxyConfig,
+    common::parsers::proxy_config::{Core, ProxyConfig},
     http::services::model::xray_config::{
-        Settings, StreamSettings, XrayClientConfig,
+        Inbound, InboundSettings, LogSettings, Outbound, OutboundServer, OutboundSettings,
+        Routing, RoutingRule, Settings, Sniffing, StreamSettings, Transport, VnextServer,
+        XrayClientConfig, XrayConfig,
     },
+    services::{CoreSettings, Group, StorageError},
 };
 
+pub const PROXY_TAG: &str = "proxy";
+pub const DIRECT_TAG: &str = "direct";
+pub const BLOCK_TAG: &str = "block";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigBuilderError {
+    #[error("{protocol} is not supported by {core}, it requires a compatible core: {cores}")]
+    IncompatibleCore {
+        protocol: String,
+        core: Core,
+        cores: String,
+    },
+
+    #[error("{network} transport is not supported by {core}")]
+    UnsupportedTransport { network: String, core: Core },
+
+    #[error("Group '{group}' has no config at index {index}")]
+    ConfigNotFound { group: String, index: usize },
+
+    #[error("Failed to read group: {0}")]
+    Storage(#[from] StorageError),
+
+    #[error("Failed to serialize core config: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("Failed to write core config: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Config at `index` of a group, the one a generated core config routes through.
+pub fn select_config(group: &Group, index: usize) -> Result<ProxyConfig, ConfigBuilderError> {
+    group
+        .proxy_configs()?
+        .into_iter()
+        .nth(index)
+        .ok_or_else(|| ConfigBuilderError::ConfigNotFound {
+            group: group.name.clone(),
+            index,
+        })
+}
+
+pub fn ensure_compatible(config: &ProxyConfig, core: Core) -> Result<(), ConfigBuilderError> {
+    if config.is_compatible_with(core) {
+        return Ok(());
+    }
+
+    Err(ConfigBuilderError::IncompatibleCore {
+        protocol: config.protocol().to_string(),
+        core,
+        cores: config
+            .compatible_cores()
+            .iter()
+            .map(|core| core.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+    })
+}
+
+impl XrayConfig {
+    /// Builds a runnable config that routes everything through `config`.
+    pub fn new(config: &ProxyConfig, settings: &CoreSettings) -> Result<Self, ConfigBuilderError> {
+        Ok(XrayConfig {
+            log: LogSettings {
+                loglevel: settings.log_level.xray().to_string(),
+                access: None,
+                error: None,
+            },
+            inbounds: vec![
+                Inbound::new("socks-in", "socks", &settings.listen, settings.socks_port),
+                Inbound::new("http-in", "http", &settings.listen, settings.http_port),
+            ],
+            outbounds: vec![
+                Outbound::proxy(config)?,
+                Outbound::empty(DIRECT_TAG, "freedom"),
+                Outbound::empty(BLOCK_TAG, "blackhole"),
+            ],
+            routing: Routing {
+                domain_strategy: String::from("IPIfNonMatch"),
+                rules: vec![RoutingRule {
+                    rule_type: String::from("field"),
+                    ip: vec![String::from("geoip:private")],
+                    domain: Vec::new(),
+                    protocol: Vec::new(),
+                    outbound_tag: DIRECT_TAG.to_string(),
+                }],
+            },
+        })
+    }
+}
+
+impl Inbound {
+    fn new(tag: &str, protocol: &str, listen: &str, port: u16) -> Self {
+        let settings = match protocol {
+            "socks" => InboundSettings {
+                auth: Some(String::from("noauth")),
+                udp: Some(true),
+            },
+            _ => InboundSettings::default(),
+        };
+
+        Inbound {
+            tag: tag.to_string(),
+            listen: listen.to_string(),
+            port,
+            protocol: protocol.to_string(),
+            settings,
+            sniffing: Some(Sniffing {
+                enabled: true,
+                dest_override: vec![String::from("http"), String::from("tls")],
+            }),
+        }
+    }
+}
+
+impl Outbound {
+    pub fn proxy(config: &ProxyConfig) -> Result<Self, ConfigBuilderError> {
+        let client = XrayClientConfig::new(config)?;
+        let Settings {
+            address,
+            port,
+            mut users,
+            method,
+            password,
+        } = client.settings;
+
+        let settings = match config.protocol() {
+            "vless" | "vmess" => {
+                if config.protocol() == "vless" {
+                    for user in users.iter_mut() {
+                        user.encryption.get_or_insert_with(|| String::from("none"));
+                    }
+                }
+
+                OutboundSettings::Vnext {
+                    vnext: vec![VnextServer {
+                        address,
+                        port,
+                        users,
+                    }],
+                }
+            }
+            _ => {
+                let user = users.into_iter().next();
+
+                OutboundSettings::Servers {
+                    servers: vec![OutboundServer {
+                        address,
+                        port,
+                        method,
+                        password: password
+                            .or_else(|| user.as_ref().and_then(|user| user.password.clone())),
+                        flow: user.and_then(|user| user.flow),
+                    }],
+                }
+            }
+        };
+
+        // shadowsocks has no stream settings and its share link scheme differs from the xray name
+        let (protocol, stream_settings) = match config.protocol() {
+            "ss" => (String::from("shadowsocks"), None),
+            _ => (client.protocol, Some(client.stream)),
+        };
+
+        Ok(Outbound {
+            tag: PROXY_TAG.to_string(),
+            protocol,
+            settings: Some(settings),
+            stream_settings,
+        })
+    }
+
+    fn empty(tag: &str, protocol: &str) -> Self {
+        Outbound {
+            tag: tag.to_string(),
+            protocol: protocol.to_string(),
+            settings: None,
+            stream_settings: None,
+        }
+    }
+}
+
 impl XrayClientConfig {
-    pub fn new(config: &ProxyConfig) -> Self {
-        XrayClientConfig {
+    pub fn new(config: &ProxyConfig) -> Result<Self, ConfigBuilderError> {
+        ensure_compatible(config, Core::Xray)?;
+
+        Ok(XrayClientConfig {
             protocol: config.protocol().to_string(),
             name_client: config.name().map(|name| name.to_string()),
             settings: Settings {
                 address: config.address().to_string(),
                 port: config.port(),
-                users: vec![config.user().unwrap().clone()],
-            },
-            stream: StreamSettings {
-                reality: config.reality_settings().cloned(),
-                network: config.network().map(|network| network.to_string()),
-                security: config.security().map(|security| security.to_string()),
+                users: config.user().cloned().into_iter().collect(),
+                method: config.method().map(|method| method.to_string()),
+                password: config.password().map(|password| password.to_string()),
             },
+            stream: StreamSettings::new(config),
+        })
+    }
+}
+
+impl StreamSettings {
+    pub fn new(config: &ProxyConfig) -> Self {
+        let mut stream = StreamSettings {
+            network: config.network().map(|network| network.to_string()),
+            security: config.security().map(|security| security.to_string()),
+            tls_settings: config.tls_settings().cloned(),
+            reality_settings: config.reality_settings().cloned(),
+            ..Default::default()
+        };
+
+        match config.transport() {
+            Some(Transport::Tcp(settings)) if settings.header.is_some() => {
+                stream.tcp_settings = Some(settings.clone())
+            }
+            Some(Transport::Ws(settings)) => stream.ws_settings = Some(settings.clone()),
+            Some(Transport::Grpc(settings)) => stream.grpc_settings = Some(settings.clone()),
+            Some(Transport::HttpUpgrade(settings)) => {
+                stream.httpupgrade_settings = Some(settings.clone())
+            }
+            Some(Transport::Xhttp(settings)) => stream.xhttp_settings = Some(settings.clone()),
+            _ => {}
         }
+
+        stream
     }
 }
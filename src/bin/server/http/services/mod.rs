@@ -1,2 +1,3 @@
 pub mod config_builder;
 pub mod model;
+pub mod sing_box_builder;
@@ -1 +1,2 @@
+pub mod sing_box_config;
 pub mod xray_config;
@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Complete document accepted by `sing-box run -c`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SingBoxConfig {
+    pub log: SingBoxLog,
+    pub inbounds: Vec<SingBoxInbound>,
+    pub outbounds: Vec<SingBoxOutbound>,
+    pub route: Route,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SingBoxLog {
+    pub level: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disabled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SingBoxInbound {
+    #[serde(rename = "type")]
+    pub inbound_type: String,
+    pub tag: String,
+    pub listen: String,
+    pub listen_port: u16,
+}
+
+/// Every outbound type shares this struct, fields a type does not use are left out.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SingBoxOutbound {
+    #[serde(rename = "type")]
+    pub outbound_type: String,
+    pub tag: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flow: Option<String>,
+    /// vmess cipher
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alter_id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin_opts: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub up_mbps: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub down_mbps: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub obfs: Option<Obfs>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub congestion_control: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub udp_relay_mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<OutboundTls>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transport: Option<V2rayTransport>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Obfs {
+    #[serde(rename = "type")]
+    pub obfs_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct OutboundTls {
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_name: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub insecure: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alpn: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utls: Option<Utls>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reality: Option<Reality>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Utls {
+    pub enabled: bool,
+    pub fingerprint: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Reality {
+    pub enabled: bool,
+    pub public_key: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub short_id: String,
+}
+
+/// Transports sing-box shares with xray, tagged by the sing-box type name.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum V2rayTransport {
+    Ws {
+        path: String,
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        headers: HashMap<String, String>,
+    },
+    Grpc {
+        service_name: String,
+    },
+    HttpUpgrade {
+        path: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        host: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Route {
+    #[serde(default)]
+    pub rules: Vec<RouteRule>,
+    #[serde(rename = "final")]
+    pub final_outbound: String,
+    #[serde(default)]
+    pub auto_detect_interface: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RouteRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_is_private: Option<bool>,
+    pub action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outbound: Option<String>,
+}
@@ -1,52 +1,318 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct RealitySettings {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fingerprint: Option<String>,
+    #[serde(alias = "public_key")]
     pub public_key: String,
+    #[serde(alias = "server_name")]
     pub server_name: String,
+    #[serde(alias = "short_id")]
     pub short_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spider_x: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alpn: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_insecure: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct GRPCSettings {
     pub service_name: String,
-    pub multi_mode: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authority: Option<String>,
+    #[serde(default)]
+    pub multi_mode: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct WsSettings {
+    #[serde(default)]
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct HttpUpgradeSettings {
+    #[serde(default)]
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct XhttpSettings {
+    #[serde(default)]
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct TcpSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<TcpHeader>,
+}
+
+/// Only the `http` obfuscation header is supported, `none` means no header at all.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct TcpHeader {
+    #[serde(rename = "type")]
+    pub header_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<TcpHeaderRequest>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct TcpHeaderRequest {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, Vec<String>>,
+}
+
+/// Transport of a config together with its settings, tagged by the xray network name.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "network", rename_all = "lowercase")]
+pub enum Transport {
+    Tcp(TcpSettings),
+    Ws(WsSettings),
+    Grpc(GRPCSettings),
+    HttpUpgrade(HttpUpgradeSettings),
+    Xhttp(XhttpSettings),
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Transport::Tcp(TcpSettings::default())
+    }
+}
+
+impl Transport {
+    pub fn network(&self) -> &'static str {
+        match self {
+            Transport::Tcp(_) => "tcp",
+            Transport::Ws(_) => "ws",
+            Transport::Grpc(_) => "grpc",
+            Transport::HttpUpgrade(_) => "httpupgrade",
+            Transport::Xhttp(_) => "xhttp",
+        }
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Transport::Tcp(settings) => settings
+                .header
+                .as_ref()
+                .and_then(|header| header.request.as_ref())
+                .and_then(|request| request.path.first())
+                .map(String::as_str),
+            Transport::Ws(settings) => Some(settings.path.as_str()),
+            Transport::Grpc(settings) => Some(settings.service_name.as_str()),
+            Transport::HttpUpgrade(settings) => Some(settings.path.as_str()),
+            Transport::Xhttp(settings) => Some(settings.path.as_str()),
+        }
+        .filter(|path| !path.is_empty())
+    }
+
+    pub fn host(&self) -> Option<&str> {
+        match self {
+            Transport::Tcp(settings) => settings
+                .header
+                .as_ref()
+                .and_then(|header| header.request.as_ref())
+                .and_then(|request| request.headers.get("Host"))
+                .and_then(|hosts| hosts.first())
+                .map(String::as_str),
+            Transport::Ws(settings) => settings.host.as_deref(),
+            Transport::Grpc(settings) => settings.authority.as_deref(),
+            Transport::HttpUpgrade(settings) => settings.host.as_deref(),
+            Transport::Xhttp(settings) => settings.host.as_deref(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Settings {
     pub address: String,
     pub port: u16,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub users: Vec<User>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct User {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub encryption: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
+    #[serde(default, alias = "alter_id", skip_serializing_if = "Option::is_none")]
+    pub alter_id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flow: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct StreamSettings {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub network: Option<String>,
-    // #[serde(skip_serializing_if = "Option::is_none")]
-    // grpc_settings: Option<GRPCSettings>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub reality: Option<RealitySettings>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub security: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_settings: Option<TlsSettings>,
+    #[serde(alias = "reality", skip_serializing_if = "Option::is_none")]
+    pub reality_settings: Option<RealitySettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tcp_settings: Option<TcpSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ws_settings: Option<WsSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc_settings: Option<GRPCSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub httpupgrade_settings: Option<HttpUpgradeSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xhttp_settings: Option<XhttpSettings>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct XrayClientConfig {
     pub protocol: String,
     pub settings: Settings,
+    #[serde(rename = "streamSettings", alias = "stream")]
     pub stream: StreamSettings,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name_client: Option<String>,
 }
+
+/// Complete document accepted by `xray run -c`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct XrayConfig {
+    pub log: LogSettings,
+    pub inbounds: Vec<Inbound>,
+    pub outbounds: Vec<Outbound>,
+    pub routing: Routing,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LogSettings {
+    pub loglevel: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Inbound {
+    pub tag: String,
+    pub listen: String,
+    pub port: u16,
+    pub protocol: String,
+    #[serde(default)]
+    pub settings: InboundSettings,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sniffing: Option<Sniffing>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct InboundSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub udp: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Sniffing {
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dest_override: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Outbound {
+    pub tag: String,
+    pub protocol: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<OutboundSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_settings: Option<StreamSettings>,
+}
+
+/// vless and vmess list their servers under `vnext`, trojan and shadowsocks under `servers`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum OutboundSettings {
+    Vnext { vnext: Vec<VnextServer> },
+    Servers { servers: Vec<OutboundServer> },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VnextServer {
+    pub address: String,
+    pub port: u16,
+    pub users: Vec<User>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OutboundServer {
+    pub address: String,
+    pub port: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flow: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Routing {
+    pub domain_strategy: String,
+    #[serde(default)]
+    pub rules: Vec<RoutingRule>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutingRule {
+    #[serde(rename = "type")]
+    pub rule_type: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ip: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub domain: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub protocol: Vec<String>,
+    pub outbound_tag: String,
+}
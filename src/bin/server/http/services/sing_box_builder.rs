@@ -0,0 +1,294 @@
+use crate::{
+    common::parsers::proxy_config::{Core, ProxyConfig},
+    http::services::{
+        config_builder::{ConfigBuilderError, DIRECT_TAG, PROXY_TAG, ensure_compatible},
+        model::{
+            sing_box_config::{
+                Obfs, OutboundTls, Reality, Route, RouteRule, SingBoxConfig, SingBoxInbound,
+                SingBoxLog, SingBoxOutbound, Utls, V2rayTransport,
+            },
+            xray_config::Transport,
+        },
+    },
+    services::{CoreSettings, LogLevel},
+};
+
+/// uTLS fingerprint used for reality when the link does not name one, sing-box requires it.
+const DEFAULT_FINGERPRINT: &str = "chrome";
+
+impl SingBoxConfig {
+    /// Builds a runnable config that routes everything through `config`, listening on the
+    /// same ports as the xray config so switching cores does not touch client settings.
+    pub fn new(config: &ProxyConfig, settings: &CoreSettings) -> Result<Self, ConfigBuilderError> {
+        Ok(SingBoxConfig {
+            log: SingBoxLog {
+                level: settings.log_level.sing_box().to_string(),
+                disabled: settings.log_level == LogLevel::None,
+            },
+            inbounds: vec![
+                SingBoxInbound::new("socks-in", "socks", &settings.listen, settings.socks_port),
+                SingBoxInbound::new("http-in", "http", &settings.listen, settings.http_port),
+            ],
+            outbounds: vec![
+                SingBoxOutbound::proxy(config)?,
+                SingBoxOutbound {
+                    outbound_type: String::from("direct"),
+                    tag: DIRECT_TAG.to_string(),
+                    ..Default::default()
+                },
+            ],
+            route: Route {
+                rules: vec![
+                    RouteRule {
+                        action: String::from("sniff"),
+                        ..Default::default()
+                    },
+                    RouteRule {
+                        ip_is_private: Some(true),
+                        action: String::from("route"),
+                        outbound: Some(DIRECT_TAG.to_string()),
+                    },
+                ],
+                final_outbound: PROXY_TAG.to_string(),
+                auto_detect_interface: true,
+            },
+        })
+    }
+}
+
+impl SingBoxInbound {
+    fn new(tag: &str, inbound_type: &str, listen: &str, port: u16) -> Self {
+        SingBoxInbound {
+            inbound_type: inbound_type.to_string(),
+            tag: tag.to_string(),
+            listen: listen.to_string(),
+            listen_port: port,
+        }
+    }
+}
+
+impl SingBoxOutbound {
+    pub fn proxy(config: &ProxyConfig) -> Result<Self, ConfigBuilderError> {
+        ensure_compatible(config, Core::SingBox)?;
+
+        let mut outbound = SingBoxOutbound {
+            outbound_type: config.protocol().to_string(),
+            tag: PROXY_TAG.to_string(),
+            server: Some(config.address().to_string()),
+            server_port: Some(config.port()),
+            ..Default::default()
+        };
+
+        let user = config.user();
+
+        match config.protocol() {
+            "vless" => {
+                outbound.uuid = config.uuid().map(String::from);
+                outbound.flow = user.and_then(|user| user.flow.clone());
+            }
+            "vmess" => {
+                outbound.uuid = config.uuid().map(String::from);
+                outbound.security = user
+                    .and_then(|user| user.security.clone())
+                    .or_else(|| Some(String::from("auto")));
+                outbound.alter_id = user.and_then(|user| user.alter_id);
+            }
+            "trojan" => {
+                outbound.password = user.and_then(|user| user.password.clone());
+            }
+            "ss" => {
+                outbound.outbound_type = String::from("shadowsocks");
+                outbound.method = config.method().map(String::from);
+                outbound.password = config.password().map(String::from);
+                outbound.plugin = config.plugin().map(String::from);
+                outbound.plugin_opts = config.plugin_opts().map(String::from);
+            }
+            "hysteria2" => {
+                outbound.password = config.password().map(String::from);
+                outbound.up_mbps = config.up_mbps();
+                outbound.down_mbps = config.down_mbps();
+                outbound.obfs = config.obfs().map(|obfs_type| Obfs {
+                    obfs_type: obfs_type.to_string(),
+                    password: config.obfs_password().map(String::from),
+                });
+                outbound.tls = Some(OutboundTls::quic(config));
+            }
+            "tuic" => {
+                outbound.uuid = config.uuid().map(String::from);
+                outbound.password = config.password().map(String::from);
+                outbound.congestion_control = config.congestion_control().map(String::from);
+                outbound.udp_relay_mode = config.udp_relay_mode().map(String::from);
+                outbound.tls = Some(OutboundTls::quic(config));
+            }
+            _ => {}
+        }
+
+        if config.transport().is_some() {
+            outbound.tls = OutboundTls::stream(config);
+            outbound.transport = V2rayTransport::new(config)?;
+        }
+
+        Ok(outbound)
+    }
+}
+
+impl OutboundTls {
+    /// hysteria2 and tuic always run over TLS and carry its settings in the link itself.
+    fn quic(config: &ProxyConfig) -> Self {
+        OutboundTls {
+            enabled: true,
+            server_name: config.sni().map(String::from),
+            insecure: config.insecure(),
+            alpn: config.alpn().to_vec(),
+            ..Default::default()
+        }
+    }
+
+    fn stream(config: &ProxyConfig) -> Option<Self> {
+        match config.security() {
+            Some("tls") => {
+                let tls = config.tls_settings().cloned().unwrap_or_default();
+
+                Some(OutboundTls {
+                    enabled: true,
+                    server_name: tls.server_name,
+                    insecure: tls.allow_insecure,
+                    alpn: tls.alpn,
+                    utls: tls.fingerprint.map(Utls::new),
+                    reality: None,
+                })
+            }
+            Some("reality") => {
+                let reality = config.reality_settings()?;
+
+                Some(OutboundTls {
+                    enabled: true,
+                    server_name: Some(reality.server_name.clone()),
+                    utls: Some(Utls::new(
+                        reality
+                            .fingerprint
+                            .clone()
+                            .unwrap_or_else(|| DEFAULT_FINGERPRINT.to_string()),
+                    )),
+                    reality: Some(Reality {
+                        enabled: true,
+                        public_key: reality.public_key.clone(),
+                        short_id: reality.short_id.clone(),
+                    }),
+                    ..Default::default()
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Utls {
+    fn new(fingerprint: String) -> Self {
+        Utls {
+            enabled: true,
+            fingerprint,
+        }
+    }
+}
+
+impl V2rayTransport {
+    fn new(config: &ProxyConfig) -> Result<Option<Self>, ConfigBuilderError> {
+        let unsupported = |network: &str| ConfigBuilderError::UnsupportedTransport {
+            network: network.to_string(),
+            core: Core::SingBox,
+        };
+
+        match config.transport() {
+            Some(Transport::Ws(settings)) => Ok(Some(V2rayTransport::Ws {
+                path: settings.path.clone(),
+                headers: settings
+                    .host
+                    .iter()
+                    .map(|host| (String::from("Host"), host.clone()))
+                    .collect(),
+            })),
+            Some(Transport::Grpc(settings)) => Ok(Some(V2rayTransport::Grpc {
+                service_name: settings.service_name.clone(),
+            })),
+            Some(Transport::HttpUpgrade(settings)) => Ok(Some(V2rayTransport::HttpUpgrade {
+                path: settings.path.clone(),
+                host: settings.host.clone(),
+            })),
+            // sing-box has no equivalent of the xray tcp http header obfuscation
+            Some(Transport::Tcp(settings)) if settings.header.is_some() => {
+                Err(unsupported("tcp with http header"))
+            }
+            Some(Transport::Xhttp(_)) => Err(unsupported("xhttp")),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::parsers::proxy_config;
+
+    fn parse(line: &str) -> ProxyConfig {
+        let mut report = proxy_config::work(line);
+        assert!(report.rejected.is_empty(), "{} was rejected", line);
+        report.configs.remove(0)
+    }
+
+    fn proxy(line: &str) -> SingBoxOutbound {
+        SingBoxOutbound::proxy(&parse(line)).unwrap_or_else(|err| panic!("{}: {}", line, err))
+    }
+
+    #[test]
+    fn hysteria2_outbound() {
+        let outbound = proxy(
+            "hysteria2://secret@example.com:443?sni=real.example.com&obfs=salamander&obfs-password=pass&insecure=1#hy2",
+        );
+
+        assert_eq!(outbound.outbound_type, "hysteria2");
+        assert_eq!(outbound.password.as_deref(), Some("secret"));
+        assert_eq!(
+            outbound.obfs,
+            Some(Obfs {
+                obfs_type: String::from("salamander"),
+                password: Some(String::from("pass")),
+            })
+        );
+
+        let tls = outbound.tls.unwrap();
+        assert_eq!(tls.server_name.as_deref(), Some("real.example.com"));
+        assert!(tls.insecure);
+    }
+
+    #[test]
+    fn vless_reality_grpc_outbound() {
+        let outbound = proxy(
+            "vless://d8737518-5251-4e25-a653-8c625ef18b8f@24.120.32.42:2040?security=reality&type=grpc&sni=unpkg.com&sid=e0969a6f81b52865&pbk=FPIcpZmVrQcqkF1vR_aBnLw_Uu4CNhuuKkrRtKpzRHg&serviceName=grpc&flow=xtls-rprx-vision#grpc",
+        );
+
+        assert_eq!(outbound.uuid.as_deref(), Some("d8737518-5251-4e25-a653-8c625ef18b8f"));
+        assert_eq!(outbound.flow.as_deref(), Some("xtls-rprx-vision"));
+        assert_eq!(
+            outbound.transport,
+            Some(V2rayTransport::Grpc {
+                service_name: String::from("grpc")
+            })
+        );
+
+        let tls = outbound.tls.unwrap();
+        assert_eq!(tls.server_name.as_deref(), Some("unpkg.com"));
+        assert_eq!(tls.utls.unwrap().fingerprint, DEFAULT_FINGERPRINT);
+        assert_eq!(tls.reality.unwrap().short_id, "e0969a6f81b52865");
+    }
+
+    #[test]
+    fn xhttp_is_rejected() {
+        let config = parse("vless://uuid@example.com:443?security=tls&type=xhttp&path=%2Fx#x");
+
+        assert!(matches!(
+            SingBoxOutbound::proxy(&config),
+            Err(ConfigBuilderError::UnsupportedTransport { .. })
+        ));
+    }
+}
@@ -1,7 +1,7 @@
 use dirs::config_dir;
-use eyre::OptionExt;
-use luxnulla::{CONFIG_DIR, SOCKET_NAME, XRAY_CONFIG_FILE};
-use std::{fs, path::PathBuf, sync::Arc};
+use eyre::{OptionExt, eyre};
+use luxnulla::{CONFIG_DIR, LUXNULLA_CONFIG_FILE};
+use std::{fs, sync::Arc};
 use tokio::net::UnixListener;
 mod client_handler;
 mod common;
@@ -15,17 +15,35 @@ async fn main() -> eyre::Result<()> {
         .ok_or_eyre("cannot get a dir")?
         .join(CONFIG_DIR);
 
-    let application = Arc::new(client_handler::ClientHandler::new(config_dir_path.clone()));
+    let settings = services::Settings::load(&config_dir_path).map_err(|e| {
+        eyre!(
+            "{}: {}",
+            config_dir_path.join(LUXNULLA_CONFIG_FILE).display(),
+            e
+        )
+    })?;
+
+    let storage = Arc::new(services::StorageService::new(settings.storage_dir.clone()));
 
     if !config_dir_path.exists() {
         std::fs::create_dir(&config_dir_path)?;
     }
 
-    if !config_dir_path.join(XRAY_CONFIG_FILE).exists() {
-        std::fs::File::create(&config_dir_path.join(XRAY_CONFIG_FILE)).unwrap();
+    let core_config_path = config_dir_path.join(settings.profile.backend().config_file());
+    if !core_config_path.exists() {
+        fs::File::create(core_config_path)?;
     }
 
-    let sock_path = PathBuf::from("/tmp/").join(SOCKET_NAME);
+    let sock_path = settings.socket_path.clone();
+    let http_bind = settings.http_bind;
+
+    // the config watcher needs the directory to exist
+    let application = Arc::new(client_handler::ClientHandler::new(
+        config_dir_path.clone(),
+        storage.clone(),
+        settings,
+    ));
+
     if sock_path.exists() {
         fs::remove_file(&sock_path)?;
     }
@@ -33,7 +51,14 @@ async fn main() -> eyre::Result<()> {
     let listener = UnixListener::bind(&sock_path)?;
     println!("Luxnulla listening on {:?}", sock_path);
 
-    let _ = http::server::init();
+    let _ = http::server::init(
+        http_bind,
+        storage,
+        application.command_handler().xray_logs(),
+        application.command_handler().config_store(),
+        application.command_handler().selector(),
+        application.command_handler().settings(),
+    );
 
     loop {
         let app_clone = application.clone();
@@ -0,0 +1,104 @@
+use serde_json::{Value as JsonValue, json};
+use std::{ffi::OsString, path::Path};
+
+use crate::{
+    common::parsers::proxy_config::{Core, ProxyConfig},
+    http::services::config_builder::ConfigBuilderError,
+    services::{CoreSettings, backend::CoreBackend},
+};
+
+/// Runs shell scripts in place of a real core, so the supervisor can be tested without xray.
+pub struct FakeBackend {
+    script: String,
+    check: String,
+    unsupported: Option<String>,
+}
+
+impl FakeBackend {
+    /// `script` is run by `sh -c` as the core process.
+    pub fn new(script: &str) -> Self {
+        Self {
+            script: script.to_string(),
+            check: String::from("exit 0"),
+            unsupported: None,
+        }
+    }
+
+    /// Makes the config test fail with `output`.
+    pub fn rejecting(mut self, output: &str) -> Self {
+        self.check = format!("echo '{}' >&2; exit 1", output);
+        self
+    }
+
+    /// Makes the config test fail for configs routing through `address`.
+    pub fn rejecting_address(mut self, address: &str) -> Self {
+        self.check = format!(
+            "if grep -q '\"{}\"' \"$1\"; then echo 'invalid address' >&2; exit 1; fi",
+            address
+        );
+        self
+    }
+
+    /// Makes rendering fail for configs using the `network` transport.
+    pub fn unsupporting(mut self, network: &str) -> Self {
+        self.unsupported = Some(network.to_string());
+        self
+    }
+}
+
+impl CoreBackend for FakeBackend {
+    fn core(&self) -> Core {
+        Core::Xray
+    }
+
+    fn binary_name(&self) -> &str {
+        "sh"
+    }
+
+    fn binary_path(&self) -> Option<&Path> {
+        None
+    }
+
+    fn config_file(&self) -> &str {
+        "fake.json"
+    }
+
+    fn render(
+        &self,
+        config: &ProxyConfig,
+        _settings: &CoreSettings,
+    ) -> Result<JsonValue, ConfigBuilderError> {
+        let unsupported = config
+            .network()
+            .filter(|network| self.unsupported.as_deref() == Some(*network));
+        if let Some(network) = unsupported {
+            return Err(ConfigBuilderError::UnsupportedTransport {
+                network: network.to_string(),
+                core: self.core(),
+            });
+        }
+
+        Ok(json!({
+            "server": config.address(),
+            "port": config.port(),
+        }))
+    }
+
+    fn run_args(&self, _config_path: &Path) -> Vec<OsString> {
+        vec!["-c".into(), self.script.clone().into()]
+    }
+
+    fn test_args(&self, config_path: &Path) -> Vec<OsString> {
+        // the config path is `$1` of the check
+        vec![
+            "-c".into(),
+            self.check.clone().into(),
+            "fake".into(),
+            config_path.into(),
+        ]
+    }
+
+    fn version_args(&self) -> Vec<OsString> {
+        vec!["-c".into(), "echo fake 1.0.0".into()]
+    }
+}
@@ -0,0 +1,173 @@
+use serde_json::Value as JsonValue;
+use std::{
+    env,
+    ffi::OsString,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+};
+use tokio::process::{Child, Command};
+
+use crate::{
+    common::parsers::proxy_config::{Core, ProxyConfig},
+    http::services::config_builder::ConfigBuilderError,
+    services::{CoreSettings, xray::logs::LogBuffer},
+};
+
+#[cfg(test)]
+pub mod fake;
+pub mod profile;
+pub mod reload;
+pub mod sing_box;
+pub mod store;
+pub mod supervisor;
+pub mod xray;
+
+pub use {profile::*, reload::*, sing_box::*, store::*, supervisor::*, xray::*};
+
+#[derive(Debug, thiserror::Error)]
+pub enum BackendError {
+    #[error("{0} was not found in PATH")]
+    BinaryNotFound(String),
+
+    #[error("{core} rejected the config: {output}")]
+    InvalidConfig { core: Core, output: String },
+
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// A proxy core luxnulla can generate configs for and supervise.
+pub trait CoreBackend: Send + Sync {
+    fn core(&self) -> Core;
+
+    /// Executable looked up in `PATH` when the profile does not point at one.
+    fn binary_name(&self) -> &str;
+
+    /// Executable set by the profile, if any.
+    fn binary_path(&self) -> Option<&Path>;
+
+    /// Name of the generated config inside the config directory.
+    fn config_file(&self) -> &str;
+
+    /// Builds a complete config that routes all traffic through `config`.
+    fn render(
+        &self,
+        config: &ProxyConfig,
+        settings: &CoreSettings,
+    ) -> Result<JsonValue, ConfigBuilderError>;
+
+    fn run_args(&self, config_path: &Path) -> Vec<OsString>;
+
+    fn test_args(&self, config_path: &Path) -> Vec<OsString>;
+
+    fn version_args(&self) -> Vec<OsString>;
+
+    fn binary(&self) -> Result<PathBuf, BackendError> {
+        match self.binary_path() {
+            Some(path) => Ok(path.to_path_buf()),
+            None => find_in_path(self.binary_name())
+                .ok_or_else(|| BackendError::BinaryNotFound(self.binary_name().to_string())),
+        }
+    }
+}
+
+impl dyn CoreBackend {
+    /// Starts the core with both output pipes feeding the log buffer.
+    pub fn spawn(&self, config_path: &Path, logs: &Arc<LogBuffer>) -> Result<Child, BackendError> {
+        let mut child = Command::new(self.binary()?)
+            .args(self.run_args(config_path))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        if let Some(stdout) = child.stdout.take() {
+            let logs = logs.clone();
+            tokio::spawn(async move { logs.capture(stdout).await });
+        }
+
+        if let Some(stderr) = child.stderr.take() {
+            let logs = logs.clone();
+            tokio::spawn(async move { logs.capture(stderr).await });
+        }
+
+        Ok(child)
+    }
+
+    /// Runs the core's config check, a rejected config comes back with the core's output.
+    pub async fn test(&self, config_path: &Path) -> Result<(), BackendError> {
+        let output = Command::new(self.binary()?)
+            .args(self.test_args(config_path))
+            .kill_on_drop(true)
+            .output()
+            .await?;
+
+        if output.status.success() {
+            return Ok(());
+        }
+
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+
+        Err(BackendError::InvalidConfig {
+            core: self.core(),
+            output: text.trim().to_string(),
+        })
+    }
+
+    /// First line the core prints for its version command.
+    pub async fn version(&self) -> Result<String, BackendError> {
+        let output = Command::new(self.binary()?)
+            .args(self.version_args())
+            .kill_on_drop(true)
+            .output()
+            .await?;
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string())
+    }
+}
+
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+
+    env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|path| {
+            path.metadata()
+                .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::backend::fake::FakeBackend;
+
+    #[tokio::test]
+    async fn binary_is_found_in_path() {
+        let backend: Arc<dyn CoreBackend> = Arc::new(FakeBackend::new("exit 0"));
+
+        assert!(backend.binary().unwrap().ends_with("sh"));
+        assert_eq!(backend.version().await.unwrap(), "fake 1.0.0");
+    }
+
+    #[tokio::test]
+    async fn rejected_config_returns_core_output() {
+        let backend: Arc<dyn CoreBackend> =
+            Arc::new(FakeBackend::new("exit 0").rejecting("unknown field: outbounds"));
+
+        match backend.test(Path::new("fake.json")).await {
+            Err(BackendError::InvalidConfig { output, .. }) => {
+                assert_eq!(output, "unknown field: outbounds")
+            }
+            other => panic!("expected a rejected config, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use std::{path::PathBuf, sync::Arc};
+
+use crate::{
+    common::parsers::proxy_config::Core,
+    services::backend::{CoreBackend, SingBoxBackend, XrayBackend},
+};
+
+/// Core selection of a profile in `luxnulla.kdl`, no profile means xray from `PATH`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub core: Core,
+    pub binary: Option<PathBuf>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: String::from("default"),
+            core: Core::Xray,
+            binary: None,
+        }
+    }
+}
+
+impl Profile {
+    pub fn backend(&self) -> Arc<dyn CoreBackend> {
+        match self.core {
+            Core::Xray => Arc::new(XrayBackend::new(self.binary.clone())),
+            Core::SingBox => Arc::new(SingBoxBackend::new(self.binary.clone())),
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use luxnulla::{CommandResponse, CoreState, ErrorCommandResponse, OkCommandResponse};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::services::{
+    backend::{ConfigStore, CoreSupervisor},
+    watch::watch_file,
+};
+
+/// Restarts the core when its live config is edited, so the change takes effect without
+/// a manual restart. The outcome is sent to every client following the daemon.
+pub struct ConfigWatcher {
+    store: Arc<ConfigStore>,
+    supervisor: Arc<CoreSupervisor>,
+    notifications: broadcast::Sender<CommandResponse>,
+}
+
+impl ConfigWatcher {
+    pub fn new(
+        store: Arc<ConfigStore>,
+        supervisor: Arc<CoreSupervisor>,
+        notifications: broadcast::Sender<CommandResponse>,
+    ) -> Self {
+        Self {
+            store,
+            supervisor,
+            notifications,
+        }
+    }
+
+    pub fn watch(self: Arc<Self>) -> notify::Result<()> {
+        let config_path = self.store.config_path().to_path_buf();
+
+        watch_file(config_path, move || {
+            let watcher = self.clone();
+            async move { watcher.reload().await }
+        })
+    }
+
+    async fn reload(&self) {
+        let file = self.store.backend().config_file().to_string();
+
+        let response = match self.store.check_edit().await {
+            Ok(false) => return,
+            Ok(true) if self.supervisor.status().state == CoreState::Stopped => {
+                CommandResponse::Ok(OkCommandResponse::Message(format!(
+                    "{} was changed, it is used on the next start",
+                    file
+                )))
+            }
+            Ok(true) => match self.supervisor.restart().await {
+                CommandResponse::Ok(OkCommandResponse::Message(message)) => CommandResponse::Ok(
+                    OkCommandResponse::Message(format!("{} was reloaded, {}", file, message)),
+                ),
+                response => response,
+            },
+            Err(e) => CommandResponse::Err(ErrorCommandResponse::Message(format!(
+                "{} was not reloaded: {}",
+                file, e
+            ))),
+        };
+
+        match &response {
+            CommandResponse::Err(ErrorCommandResponse::Message(message)) => {
+                eprintln!("{}", message)
+            }
+            CommandResponse::Ok(OkCommandResponse::Message(message)) => println!("{}", message),
+            _ => {}
+        }
+
+        // nobody following is not an error
+        let _ = self.notifications.send(response);
+    }
+}
@@ -0,0 +1,62 @@
+use luxnulla::SING_BOX_CONFIG_FILE;
+use serde_json::Value as JsonValue;
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    common::parsers::proxy_config::{Core, ProxyConfig},
+    http::services::{
+        config_builder::ConfigBuilderError, model::sing_box_config::SingBoxConfig,
+    },
+    services::{CoreSettings, backend::CoreBackend},
+};
+
+pub struct SingBoxBackend {
+    binary: Option<PathBuf>,
+}
+
+impl SingBoxBackend {
+    pub fn new(binary: Option<PathBuf>) -> Self {
+        Self { binary }
+    }
+}
+
+impl CoreBackend for SingBoxBackend {
+    fn core(&self) -> Core {
+        Core::SingBox
+    }
+
+    fn binary_name(&self) -> &str {
+        "sing-box"
+    }
+
+    fn binary_path(&self) -> Option<&Path> {
+        self.binary.as_deref()
+    }
+
+    fn config_file(&self) -> &str {
+        SING_BOX_CONFIG_FILE
+    }
+
+    fn render(
+        &self,
+        config: &ProxyConfig,
+        settings: &CoreSettings,
+    ) -> Result<JsonValue, ConfigBuilderError> {
+        Ok(serde_json::to_value(SingBoxConfig::new(config, settings)?)?)
+    }
+
+    fn run_args(&self, config_path: &Path) -> Vec<OsString> {
+        vec!["run".into(), "-c".into(), config_path.into()]
+    }
+
+    fn test_args(&self, config_path: &Path) -> Vec<OsString> {
+        vec!["check".into(), "-c".into(), config_path.into()]
+    }
+
+    fn version_args(&self) -> Vec<OsString> {
+        vec!["version".into()]
+    }
+}
@@ -0,0 +1,354 @@
+use chrono::Utc;
+use serde_json::Value as JsonValue;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    common::parsers::proxy_config::ProxyConfig,
+    http::services::config_builder::ConfigBuilderError,
+    services::{
+        CoreSettings,
+        backend::{BackendError, CoreBackend},
+    },
+};
+
+/// Directory inside the config dir holding configs that were replaced.
+const HISTORY_DIR: &str = "history";
+
+/// Number of replaced configs kept for `rollback`.
+pub const KNOWN_GOOD_CONFIGS: usize = 5;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApplyError {
+    #[error(transparent)]
+    Build(#[from] ConfigBuilderError),
+
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+
+    #[error("invalid JSON: {0}")]
+    Json(serde_json::Error),
+
+    #[error("{error}, the last accepted config was restored and the edit kept in {kept:?}")]
+    EditRejected { error: BackendError, kept: PathBuf },
+
+    #[error("cannot roll back {steps} configs, only {kept} are kept")]
+    NothingToRollBack { steps: usize, kept: usize },
+
+    #[error("Failed to replace config: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// The live config of the core: it is only ever replaced by a candidate the core accepted,
+/// and the configs it replaced are kept so they can be restored.
+pub struct ConfigStore {
+    backend: Arc<dyn CoreBackend>,
+    settings: Mutex<CoreSettings>,
+    config_path: PathBuf,
+    history_dir: PathBuf,
+    /// Content of the live config as last written or accepted, edits are compared against it.
+    known: Mutex<Option<Vec<u8>>>,
+}
+
+impl ConfigStore {
+    pub fn new(backend: Arc<dyn CoreBackend>, settings: CoreSettings, config_dir: &Path) -> Self {
+        let config_path = config_dir.join(backend.config_file());
+
+        Self {
+            known: Mutex::new(fs::read(&config_path).ok()),
+            config_path,
+            history_dir: config_dir.join(HISTORY_DIR),
+            backend,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    pub fn backend(&self) -> Arc<dyn CoreBackend> {
+        self.backend.clone()
+    }
+
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
+    }
+
+    /// Used by the next `apply`, the live config is not touched.
+    pub fn set_settings(&self, settings: CoreSettings) {
+        *self.settings.lock().unwrap() = settings;
+    }
+
+    /// Core config routing through `config` with the current settings, nothing is written.
+    pub fn render(&self, config: &ProxyConfig) -> Result<JsonValue, ConfigBuilderError> {
+        let settings = self.settings.lock().unwrap().clone();
+        self.backend.render(config, &settings)
+    }
+
+    /// Renders `config` and makes it the live config if the core accepts it,
+    /// returning the written document.
+    pub async fn apply(&self, config: &ProxyConfig) -> Result<JsonValue, ApplyError> {
+        let document = self.render(config)?;
+        let content = serde_json::to_string_pretty(&document).map_err(ConfigBuilderError::from)?;
+
+        self.replace(content.as_bytes(), true).await?;
+
+        Ok(document)
+    }
+
+    /// Makes a config edited by a client the live config if it is JSON the core accepts.
+    pub async fn save(&self, content: &str) -> Result<(), ApplyError> {
+        serde_json::from_str::<JsonValue>(content).map_err(ApplyError::Json)?;
+
+        self.replace(content.as_bytes(), true).await
+    }
+
+    /// Restores the config that was live `steps` replacements ago and forgets the newer ones.
+    pub async fn rollback(&self, steps: usize) -> Result<PathBuf, ApplyError> {
+        let history = self.history()?;
+        if steps == 0 || steps > history.len() {
+            return Err(ApplyError::NothingToRollBack {
+                steps,
+                kept: history.len(),
+            });
+        }
+
+        let newer = &history[history.len() - steps..];
+        let restored = newer[0].clone();
+
+        self.replace(&fs::read(&restored)?, false).await?;
+
+        for snapshot in newer {
+            fs::remove_file(snapshot)?;
+        }
+
+        Ok(restored)
+    }
+
+    /// Tests a live config that was edited outside the daemon. Returns false when the content
+    /// is what the store already knows, an accepted edit keeps the content it replaced.
+    /// A rejected edit is moved aside and the known content written back, the core is
+    /// respawned from the live config and must not loop on one it cannot run.
+    pub async fn check_edit(&self) -> Result<bool, ApplyError> {
+        let content = fs::read(&self.config_path)?;
+
+        // an empty file is what the daemon creates on the first start
+        if content.is_empty() || self.known.lock().unwrap().as_ref() == Some(&content) {
+            return Ok(false);
+        }
+
+        match self.backend.test(&self.config_path).await {
+            Ok(()) => {}
+            Err(error @ BackendError::InvalidConfig { .. }) => {
+                let known = self.known.lock().unwrap().clone();
+                let Some(known) = known.filter(|known| !known.is_empty()) else {
+                    return Err(error.into());
+                };
+
+                let kept = self.config_path.with_extension("rejected.json");
+                fs::write(&kept, &content)?;
+                fs::write(&self.config_path, known)?;
+
+                return Err(ApplyError::EditRejected { error, kept });
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        let previous = self.known.lock().unwrap().replace(content);
+        if let Some(previous) = previous.filter(|previous| !previous.is_empty()) {
+            self.snapshot(&previous)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Writes a candidate next to the live config, runs the core's test on it and renames it
+    /// into place, so a rejected config never touches the live one.
+    async fn replace(&self, content: &[u8], keep_previous: bool) -> Result<(), ApplyError> {
+        // the core picks the config format by extension, the candidate keeps `.json`
+        let candidate = self.config_path.with_extension("candidate.json");
+        fs::write(&candidate, content)?;
+
+        if let Err(e) = self.backend.test(&candidate).await {
+            let _ = fs::remove_file(&candidate);
+            return Err(e.into());
+        }
+
+        if keep_previous {
+            match fs::read(&self.config_path) {
+                Ok(previous) if !previous.is_empty() => self.snapshot(&previous)?,
+                _ => {}
+            }
+        }
+
+        // set before the rename so the watcher never takes the daemon's own write for an edit
+        *self.known.lock().unwrap() = Some(content.to_vec());
+        fs::rename(&candidate, &self.config_path)?;
+
+        Ok(())
+    }
+
+    fn snapshot(&self, content: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.history_dir)?;
+        fs::write(
+            self.history_dir.join(format!(
+                "{}{}.json",
+                self.snapshot_prefix(),
+                Utc::now().format("%Y%m%dT%H%M%S%.6f")
+            )),
+            content,
+        )?;
+
+        let history = self.history()?;
+        for snapshot in &history[..history.len().saturating_sub(KNOWN_GOOD_CONFIGS)] {
+            fs::remove_file(snapshot)?;
+        }
+
+        Ok(())
+    }
+
+    /// Replaced configs of this core, oldest first.
+    pub fn history(&self) -> io::Result<Vec<PathBuf>> {
+        let entries = match fs::read_dir(&self.history_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let prefix = self.snapshot_prefix();
+        let mut history = Vec::new();
+
+        for entry in entries {
+            let path = entry?.path();
+            let is_snapshot = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".json"));
+
+            if is_snapshot {
+                history.push(path);
+            }
+        }
+
+        // timestamps sort in the order the snapshots were taken
+        history.sort();
+
+        Ok(history)
+    }
+
+    /// Snapshots of each core are kept side by side, `xray-` or `sing-box-`.
+    fn snapshot_prefix(&self) -> String {
+        let stem = Path::new(self.backend.config_file())
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("config");
+
+        format!("{}-", stem)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{common::parsers::proxy_config, services::backend::fake::FakeBackend};
+
+    fn parse(line: &str) -> ProxyConfig {
+        proxy_config::work(line).configs.remove(0)
+    }
+
+    fn fake_store(backend: FakeBackend, config_dir: &Path) -> ConfigStore {
+        ConfigStore::new(Arc::new(backend), CoreSettings::default(), config_dir)
+    }
+
+    fn live(store: &ConfigStore) -> String {
+        fs::read_to_string(store.config_path()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn rejected_config_keeps_live_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = fake_store(FakeBackend::new("exit 0"), dir.path());
+        store.apply(&parse("trojan://pass@a.com:443#a")).await.unwrap();
+
+        let rejecting = fake_store(
+            FakeBackend::new("exit 0").rejecting("invalid outbound"),
+            dir.path(),
+        );
+
+        match rejecting.apply(&parse("trojan://pass@b.com:443#b")).await {
+            Err(ApplyError::Backend(BackendError::InvalidConfig { output, .. })) => {
+                assert_eq!(output, "invalid outbound")
+            }
+            other => panic!("expected a rejected config, got {:?}", other),
+        }
+
+        assert!(live(&store).contains("a.com"));
+        assert!(store.history().unwrap().is_empty());
+        assert!(!store.config_path().with_extension("candidate.json").exists());
+    }
+
+    #[tokio::test]
+    async fn rollback_restores_replaced_configs() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = fake_store(FakeBackend::new("exit 0"), dir.path());
+
+        for index in 0..KNOWN_GOOD_CONFIGS + 2 {
+            let line = format!("trojan://pass@server{}.com:443#{}", index, index);
+            store.apply(&parse(&line)).await.unwrap();
+        }
+
+        assert_eq!(store.history().unwrap().len(), KNOWN_GOOD_CONFIGS);
+
+        store.rollback(1).await.unwrap();
+        assert!(live(&store).contains(&format!("server{}.com", KNOWN_GOOD_CONFIGS)));
+
+        store.rollback(2).await.unwrap();
+        assert!(live(&store).contains(&format!("server{}.com", KNOWN_GOOD_CONFIGS - 2)));
+        assert_eq!(store.history().unwrap().len(), KNOWN_GOOD_CONFIGS - 3);
+
+        assert!(matches!(
+            store.rollback(KNOWN_GOOD_CONFIGS).await,
+            Err(ApplyError::NothingToRollBack { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn edits_are_checked_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = fake_store(FakeBackend::new("exit 0"), dir.path());
+        store.apply(&parse("trojan://pass@a.com:443#a")).await.unwrap();
+
+        assert!(!store.check_edit().await.unwrap());
+
+        fs::write(store.config_path(), r#"{"server": "b.com"}"#).unwrap();
+        assert!(store.check_edit().await.unwrap());
+        assert!(!store.check_edit().await.unwrap());
+
+        store.rollback(1).await.unwrap();
+        assert!(live(&store).contains("a.com"));
+    }
+
+    #[tokio::test]
+    async fn rejected_edit_is_moved_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = fake_store(FakeBackend::new("exit 0"), dir.path());
+        store.apply(&parse("trojan://pass@a.com:443#a")).await.unwrap();
+
+        let rejecting = fake_store(
+            FakeBackend::new("exit 0").rejecting("invalid outbound"),
+            dir.path(),
+        );
+        let edit = r#"{"server": "b.com"}"#;
+        fs::write(store.config_path(), edit).unwrap();
+
+        match rejecting.check_edit().await {
+            Err(ApplyError::EditRejected { kept, .. }) => {
+                assert_eq!(fs::read_to_string(kept).unwrap(), edit)
+            }
+            other => panic!("expected a rejected edit, got {:?}", other),
+        }
+
+        assert!(live(&store).contains("a.com"));
+        assert!(!rejecting.check_edit().await.unwrap());
+    }
+}
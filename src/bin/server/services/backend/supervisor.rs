@@ -0,0 +1,431 @@
+use luxnulla::{CommandResponse, CoreState, CoreStatus, ErrorCommandResponse, OkCommandResponse};
+use nix::{
+    sys::signal::{Signal, kill},
+    unistd::Pid,
+};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    process::Child,
+    sync::{Mutex as AsyncMutex, oneshot},
+};
+
+use crate::services::{
+    backend::{BackendError, CoreBackend},
+    xray::logs::LogBuffer,
+};
+
+/// Time the core gets to exit after SIGTERM before it is killed.
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A process that stayed up this long is considered healthy and resets the backoff.
+const STABLE_UPTIME: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SupervisorError {
+    #[error("the core is already {0}")]
+    AlreadyRunning(CoreState),
+
+    #[error("the core is not running")]
+    NotRunning,
+
+    #[error("{0}")]
+    Backend(#[from] BackendError),
+}
+
+/// Sent to the monitor task, which answers once the child has exited.
+type StopRequest = oneshot::Sender<()>;
+
+struct Shared {
+    status: CoreStatus,
+    started_at: Option<Instant>,
+    stop_tx: Option<oneshot::Sender<StopRequest>>,
+}
+
+/// Owns the core process: the child lives in a monitor task that restarts it after crashes
+/// and terminates it on request.
+pub struct CoreSupervisor {
+    backend: Arc<dyn CoreBackend>,
+    config_path: PathBuf,
+    logs: Arc<LogBuffer>,
+    shared: Arc<Mutex<Shared>>,
+    /// Serializes start, stop and restart so a restart is never interleaved with another command.
+    operation: AsyncMutex<()>,
+}
+
+impl CoreSupervisor {
+    pub fn new(backend: Arc<dyn CoreBackend>, config_path: PathBuf, logs: Arc<LogBuffer>) -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(Shared {
+                status: CoreStatus {
+                    backend: backend.core().to_string(),
+                    version: None,
+                    state: CoreState::Stopped,
+                    pid: None,
+                    uptime_secs: None,
+                    restart_count: 0,
+                    crash_count: 0,
+                    last_exit: None,
+                },
+                started_at: None,
+                stop_tx: None,
+            })),
+            backend,
+            config_path,
+            logs,
+            operation: AsyncMutex::new(()),
+        }
+    }
+
+    /// Config the core is started with.
+    pub fn config_path(&self) -> &PathBuf {
+        &self.config_path
+    }
+
+    pub fn logs(&self) -> Arc<LogBuffer> {
+        self.logs.clone()
+    }
+
+    pub fn status(&self) -> CoreStatus {
+        let shared = self.shared.lock().unwrap();
+
+        CoreStatus {
+            uptime_secs: shared.started_at.map(|started_at| started_at.elapsed().as_secs()),
+            ..shared.status.clone()
+        }
+    }
+
+    pub async fn start(&self) -> CommandResponse {
+        let _operation = self.operation.lock().await;
+
+        let started = match self.prepare().await {
+            Ok(()) => self.spawn_monitor(),
+            Err(e) => Err(e.into()),
+        };
+
+        match started {
+            Ok(pid) => CommandResponse::Ok(OkCommandResponse::Message(format!(
+                "{} is started with pid {}",
+                self.backend.core(),
+                pid
+            ))),
+            Err(e) => CommandResponse::Err(ErrorCommandResponse::Message(format!(
+                "Failed to start {}: {}",
+                self.backend.core(),
+                e
+            ))),
+        }
+    }
+
+    pub async fn stop(&self) -> CommandResponse {
+        let _operation = self.operation.lock().await;
+
+        match self.terminate().await {
+            Ok(()) => CommandResponse::Ok(OkCommandResponse::Message(format!(
+                "{} is stopped",
+                self.backend.core()
+            ))),
+            Err(e) => CommandResponse::Err(ErrorCommandResponse::Message(format!(
+                "Failed to stop {}: {}",
+                self.backend.core(),
+                e
+            ))),
+        }
+    }
+
+    pub async fn restart(&self) -> CommandResponse {
+        let _operation = self.operation.lock().await;
+
+        // a rejected config must not take down the running core
+        if let Err(e) = self.prepare().await {
+            return CommandResponse::Err(ErrorCommandResponse::Message(format!(
+                "Failed to restart {}: {}",
+                self.backend.core(),
+                e
+            )));
+        }
+
+        let previous = {
+            let mut shared = self.shared.lock().unwrap();
+            std::mem::replace(&mut shared.status.state, CoreState::Restarting)
+        };
+
+        match self.terminate().await {
+            Ok(()) | Err(SupervisorError::NotRunning) => {}
+            Err(e) => {
+                self.shared.lock().unwrap().status.state = previous;
+                return CommandResponse::Err(ErrorCommandResponse::Message(format!(
+                    "Failed to restart {}: {}",
+                    self.backend.core(),
+                    e
+                )));
+            }
+        }
+
+        match self.spawn_monitor() {
+            Ok(pid) => {
+                self.shared.lock().unwrap().status.restart_count += 1;
+
+                CommandResponse::Ok(OkCommandResponse::Message(format!(
+                    "{} is restarted with pid {}",
+                    self.backend.core(),
+                    pid
+                )))
+            }
+            Err(e) => CommandResponse::Err(ErrorCommandResponse::Message(format!(
+                "Failed to restart {}: {}",
+                self.backend.core(),
+                e
+            ))),
+        }
+    }
+
+    /// Tests the config and queries the version, the binary may have been upgraded
+    /// since the last start.
+    async fn prepare(&self) -> Result<(), BackendError> {
+        self.backend.test(&self.config_path).await?;
+
+        let version = self.backend.version().await.ok();
+        self.shared.lock().unwrap().status.version = version;
+
+        Ok(())
+    }
+
+    fn spawn_monitor(&self) -> Result<u32, SupervisorError> {
+        let mut shared = self.shared.lock().unwrap();
+
+        if shared.stop_tx.is_some() {
+            return Err(SupervisorError::AlreadyRunning(shared.status.state));
+        }
+
+        let child = self.backend.spawn(&self.config_path, &self.logs).inspect_err(|_| {
+            shared.status.state = CoreState::Stopped;
+        })?;
+        let pid = child.id().unwrap_or_default();
+        let (stop_tx, stop_rx) = oneshot::channel();
+
+        shared.stop_tx = Some(stop_tx);
+        shared.started_at = Some(Instant::now());
+        shared.status.state = CoreState::Running;
+        shared.status.pid = Some(pid);
+
+        tokio::spawn(monitor(
+            self.backend.clone(),
+            self.config_path.clone(),
+            self.logs.clone(),
+            self.shared.clone(),
+            child,
+            stop_rx,
+        ));
+
+        Ok(pid)
+    }
+
+    async fn terminate(&self) -> Result<(), SupervisorError> {
+        let stop_tx = self
+            .shared
+            .lock()
+            .unwrap()
+            .stop_tx
+            .take()
+            .ok_or(SupervisorError::NotRunning)?;
+
+        let (done_tx, done_rx) = oneshot::channel();
+        if stop_tx.send(done_tx).is_ok() {
+            let _ = done_rx.await;
+        }
+
+        let mut shared = self.shared.lock().unwrap();
+        if shared.status.state != CoreState::Restarting {
+            shared.status.state = CoreState::Stopped;
+        }
+        shared.status.pid = None;
+        shared.started_at = None;
+
+        Ok(())
+    }
+}
+
+/// Waits for the child to exit, restarting it with exponential backoff until a stop is requested.
+async fn monitor(
+    backend: Arc<dyn CoreBackend>,
+    config_path: PathBuf,
+    logs: Arc<LogBuffer>,
+    shared: Arc<Mutex<Shared>>,
+    child: Child,
+    mut stop_rx: oneshot::Receiver<StopRequest>,
+) {
+    let mut child: Result<Child, String> = Ok(child);
+    let mut started_at = Instant::now();
+    let mut backoff = INITIAL_BACKOFF;
+
+    loop {
+        let exit = match child.as_mut() {
+            Ok(running) => tokio::select! {
+                status = running.wait() => match status {
+                    Ok(status) => status.to_string(),
+                    Err(e) => e.to_string(),
+                },
+                request = &mut stop_rx => {
+                    stop_child(running).await;
+                    if let Ok(done_tx) = request {
+                        let _ = done_tx.send(());
+                    }
+                    return;
+                }
+            },
+            Err(e) => e.clone(),
+        };
+
+        if started_at.elapsed() >= STABLE_UPTIME {
+            backoff = INITIAL_BACKOFF;
+        }
+
+        let message = format!(
+            "{} exited unexpectedly ({}), restarting in {:?}",
+            backend.core(),
+            exit,
+            backoff
+        );
+        eprintln!("{}", message);
+        logs.push(format!("luxnulla: {}", message));
+
+        {
+            let mut shared = shared.lock().unwrap();
+            shared.status.state = CoreState::BackingOff;
+            shared.status.pid = None;
+            shared.status.crash_count += 1;
+            shared.started_at = None;
+            shared.status.last_exit = Some(exit);
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            request = &mut stop_rx => {
+                if let Ok(done_tx) = request {
+                    let _ = done_tx.send(());
+                }
+                return;
+            }
+        }
+
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+        started_at = Instant::now();
+
+        child = backend
+            .spawn(&config_path, &logs)
+            .map_err(|e| format!("failed to respawn: {}", e));
+
+        if let Ok(respawned) = &child {
+            let mut shared = shared.lock().unwrap();
+            shared.status.state = CoreState::Running;
+            shared.status.pid = respawned.id();
+            shared.status.restart_count += 1;
+            shared.started_at = Some(Instant::now());
+        }
+    }
+}
+
+/// Sends SIGTERM and falls back to SIGKILL when the core does not exit in time.
+async fn stop_child(child: &mut Child) {
+    if let Some(pid) = child.id() {
+        let _ = kill(Pid::from_raw(pid as i32), Signal::SIGTERM);
+
+        if tokio::time::timeout(STOP_TIMEOUT, child.wait()).await.is_ok() {
+            return;
+        }
+    }
+
+    let _ = child.kill().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::{backend::fake::FakeBackend, xray::logs::LOG_BUFFER_LINES};
+
+    fn supervisor(script: &str) -> CoreSupervisor {
+        CoreSupervisor::new(
+            Arc::new(FakeBackend::new(script)),
+            PathBuf::from("fake.json"),
+            Arc::new(LogBuffer::new(LOG_BUFFER_LINES, None)),
+        )
+    }
+
+    #[tokio::test]
+    async fn start_and_stop() {
+        let supervisor = supervisor("echo ready; exec sleep 30");
+
+        assert!(matches!(supervisor.start().await, CommandResponse::Ok(_)));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let status = supervisor.status();
+        assert_eq!(status.state, CoreState::Running);
+        assert!(status.pid.is_some());
+        assert_eq!(status.version.as_deref(), Some("fake 1.0.0"));
+
+        assert!(matches!(supervisor.start().await, CommandResponse::Err(_)));
+
+        assert!(matches!(supervisor.stop().await, CommandResponse::Ok(_)));
+        let status = supervisor.status();
+        assert_eq!(status.state, CoreState::Stopped);
+        assert_eq!(status.pid, None);
+        assert_eq!(status.crash_count, 0);
+        assert_eq!(supervisor.logs().tail(1), vec![String::from("ready")]);
+    }
+
+    #[tokio::test]
+    async fn crashed_core_is_restarted() {
+        let supervisor = supervisor("exit 3");
+        supervisor.start().await;
+
+        tokio::time::sleep(INITIAL_BACKOFF + Duration::from_millis(500)).await;
+
+        let status = supervisor.status();
+        assert!(status.crash_count >= 1);
+        assert!(status.restart_count >= 1);
+        assert!(status.last_exit.unwrap().contains('3'));
+
+        assert!(matches!(supervisor.stop().await, CommandResponse::Ok(_)));
+        assert_eq!(supervisor.status().state, CoreState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn restart_replaces_the_running_core() {
+        let supervisor = supervisor("exec sleep 30");
+        supervisor.start().await;
+        let pid = supervisor.status().pid;
+
+        assert!(matches!(supervisor.restart().await, CommandResponse::Ok(_)));
+
+        let status = supervisor.status();
+        assert_eq!(status.state, CoreState::Running);
+        assert_eq!(status.restart_count, 1);
+        assert_ne!(status.pid, pid);
+
+        supervisor.stop().await;
+    }
+
+    #[tokio::test]
+    async fn rejected_config_is_not_started() {
+        let supervisor = CoreSupervisor::new(
+            Arc::new(FakeBackend::new("exec sleep 30").rejecting("bad outbound")),
+            PathBuf::from("fake.json"),
+            Arc::new(LogBuffer::new(LOG_BUFFER_LINES, None)),
+        );
+
+        match supervisor.start().await {
+            CommandResponse::Err(ErrorCommandResponse::Message(message)) => {
+                assert!(message.contains("bad outbound"), "{}", message)
+            }
+            _ => panic!("a rejected config was started"),
+        }
+        assert_eq!(supervisor.status().state, CoreState::Stopped);
+    }
+}
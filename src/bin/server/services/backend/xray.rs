@@ -0,0 +1,60 @@
+use luxnulla::XRAY_CONFIG_FILE;
+use serde_json::Value as JsonValue;
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    common::parsers::proxy_config::{Core, ProxyConfig},
+    http::services::{config_builder::ConfigBuilderError, model::xray_config::XrayConfig},
+    services::{CoreSettings, backend::CoreBackend},
+};
+
+pub struct XrayBackend {
+    binary: Option<PathBuf>,
+}
+
+impl XrayBackend {
+    pub fn new(binary: Option<PathBuf>) -> Self {
+        Self { binary }
+    }
+}
+
+impl CoreBackend for XrayBackend {
+    fn core(&self) -> Core {
+        Core::Xray
+    }
+
+    fn binary_name(&self) -> &str {
+        "xray"
+    }
+
+    fn binary_path(&self) -> Option<&Path> {
+        self.binary.as_deref()
+    }
+
+    fn config_file(&self) -> &str {
+        XRAY_CONFIG_FILE
+    }
+
+    fn render(
+        &self,
+        config: &ProxyConfig,
+        settings: &CoreSettings,
+    ) -> Result<JsonValue, ConfigBuilderError> {
+        Ok(serde_json::to_value(XrayConfig::new(config, settings)?)?)
+    }
+
+    fn run_args(&self, config_path: &Path) -> Vec<OsString> {
+        vec!["run".into(), "-c".into(), config_path.into()]
+    }
+
+    fn test_args(&self, config_path: &Path) -> Vec<OsString> {
+        vec!["run".into(), "-test".into(), "-c".into(), config_path.into()]
+    }
+
+    fn version_args(&self) -> Vec<OsString> {
+        vec!["version".into()]
+    }
+}
@@ -1,8 +1,5 @@
-use luxnulla::{
-    CommandResponse, EDITOR_NAME, ErrorCommandResponse, LUXNULLA_CONFIG_FILE, OkCommandResponse,
-    XRAY_CONFIG_FILE,
-};
-use std::path::PathBuf;
+use luxnulla::{ACTIVE_FILE, ActiveSelection, LUXNULLA_CONFIG_FILE, XRAY_CONFIG_FILE};
+use std::{fs, path::PathBuf};
 
 pub struct ConfigService {
     config_dir: PathBuf,
@@ -13,40 +10,6 @@ impl ConfigService {
         Self { config_dir }
     }
 
-    pub async fn edit_xray_config(&self) -> CommandResponse {
-        match self.spawn_editor(XRAY_CONFIG_FILE).await {
-            Ok(_) => CommandResponse::Ok(OkCommandResponse::Message(String::from(
-                "zeditor is running",
-            ))),
-            Err(e) => CommandResponse::Err(ErrorCommandResponse::Message(format!(
-                "Failed to open editor: {}",
-                e
-            ))),
-        }
-    }
-
-    pub async fn edit_luxnulla_config(&self) -> CommandResponse {
-        match self.spawn_editor(LUXNULLA_CONFIG_FILE).await {
-            Ok(_) => CommandResponse::Ok(OkCommandResponse::Message(String::from(
-                "zeditor is running",
-            ))),
-            Err(e) => CommandResponse::Err(ErrorCommandResponse::Message(format!(
-                "Failed to open editor: {}",
-                e
-            ))),
-        }
-    }
-
-    async fn spawn_editor(
-        &self,
-        config_file: &str,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        tokio::process::Command::new(EDITOR_NAME)
-            .arg(&self.config_dir.join(config_file))
-            .spawn()?;
-        Ok(())
-    }
-
     pub fn get_config_path(&self, config_file: &str) -> Result<String, String> {
         self.config_dir
             .join(config_file)
@@ -55,6 +18,17 @@ impl ConfigService {
             .ok_or_else(|| "Invalid config path".to_string())
     }
 
+    /// The selection the current core config was generated from, if any.
+    pub fn load_active(&self) -> Option<ActiveSelection> {
+        let content = fs::read_to_string(self.config_dir.join(ACTIVE_FILE)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save_active(&self, active: &ActiveSelection) -> Result<(), std::io::Error> {
+        let json_data = serde_json::to_string_pretty(active)?;
+        fs::write(self.config_dir.join(ACTIVE_FILE), json_data)
+    }
+
     pub fn get_xray_config_path(&self) -> Result<String, String> {
         let config_path_buf = self.config_dir.join(XRAY_CONFIG_FILE);
         match config_path_buf.to_str() {
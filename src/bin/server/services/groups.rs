@@ -0,0 +1,190 @@
+use chrono::Utc;
+use luxnulla::{
+    CommandResponse, ErrorCommandResponse, GroupDetails, OkCommandResponse, ServerSummary,
+};
+use serde_json::json;
+use std::{path::PathBuf, sync::Arc};
+
+use crate::{
+    common::parsers::proxy_config::RejectedLine,
+    services::{
+        ConfigService, Group, StorageService,
+        subscriptions::refresh_group,
+        xray::fetcher::{ConfigType, determine_config_type, process_config},
+    },
+};
+
+/// Group management for socket clients, the HTTP API has its own handlers.
+pub struct GroupService {
+    config_service: ConfigService,
+    storage: Arc<StorageService>,
+}
+
+impl GroupService {
+    pub fn new(config_dir: PathBuf, storage: Arc<StorageService>) -> Self {
+        Self {
+            config_service: ConfigService::new(config_dir),
+            storage,
+        }
+    }
+
+    pub fn list(&self) -> CommandResponse {
+        match self.storage.get_all_groups() {
+            Ok(groups) => {
+                let mut groups = groups.iter().map(Group::status).collect::<Vec<_>>();
+                groups.sort_by(|a, b| a.name.cmp(&b.name));
+
+                CommandResponse::Ok(OkCommandResponse::Groups(groups))
+            }
+            Err(e) => error(format!("Failed to read groups: {}", e)),
+        }
+    }
+
+    pub fn show(&self, name: &str) -> CommandResponse {
+        let group = match self.storage.get_group(name) {
+            Ok(Some(group)) => group,
+            Ok(None) => return error(format!("Group '{}' not found", name)),
+            Err(e) => return error(format!("Failed to read group '{}': {}", name, e)),
+        };
+        let configs = match group.proxy_configs() {
+            Ok(configs) => configs,
+            Err(e) => return error(format!("Failed to read group '{}': {}", name, e)),
+        };
+
+        let active = self
+            .config_service
+            .load_active()
+            .filter(|active| active.group == group.name)
+            .map(|active| active.index);
+
+        CommandResponse::Ok(OkCommandResponse::Group(GroupDetails {
+            name: group.name,
+            source: group.source,
+            refreshed_at: group.refreshed_at,
+            servers: configs
+                .iter()
+                .map(|config| ServerSummary {
+                    name: config.name().map(String::from),
+                    protocol: config.protocol().to_string(),
+                    address: config.address().to_string(),
+                    port: config.port(),
+                })
+                .collect(),
+            active,
+        }))
+    }
+
+    /// Creates a group from a subscription URL or pasted configs, the same payloads
+    /// `POST /groups` takes.
+    pub async fn add(&self, name: &str, payload: &str) -> CommandResponse {
+        match self.storage.get_group(name) {
+            Ok(Some(_)) => return error(format!("Group '{}' already exists", name)),
+            Ok(None) => {}
+            Err(e) => return error(format!("Failed to read groups: {}", e)),
+        }
+
+        let report = match process_config(payload).await {
+            Ok(report) => report,
+            Err(e) => return error(format!("Invalid config format: {}", e)),
+        };
+        if report.configs.is_empty() {
+            return error(format!(
+                "No configs found, {} lines were rejected{}",
+                report.rejected.len(),
+                rejected_lines(&report.rejected)
+            ));
+        }
+
+        let mut group = Group::new(name.to_string(), json!(report.configs));
+        group.usage = report.usage.clone();
+        group.refreshed_at = Some(Utc::now());
+        if let Ok(ConfigType::Url) = determine_config_type(payload) {
+            group.source = Some(payload.trim().to_string());
+        }
+
+        if let Err(e) = self.storage.add_group(group) {
+            return error(format!("Failed to add group '{}': {}", name, e));
+        }
+
+        let mut message = format!(
+            "Group '{}' was added with {} configs",
+            name,
+            report.configs.len()
+        );
+        if !report.rejected.is_empty() {
+            message.push_str(&format!(
+                ", {} lines were rejected{}",
+                report.rejected.len(),
+                rejected_lines(&report.rejected)
+            ));
+        }
+
+        CommandResponse::Ok(OkCommandResponse::Message(message))
+    }
+
+    /// The core keeps running the config of a removed active group until another server
+    /// is selected.
+    pub fn remove(&self, name: &str) -> CommandResponse {
+        match self.storage.delete_group(name) {
+            Ok(true) => {}
+            Ok(false) => return error(format!("Group '{}' not found", name)),
+            Err(e) => return error(format!("Failed to remove group '{}': {}", name, e)),
+        }
+
+        let message = match self.config_service.load_active() {
+            Some(active) if active.group == name => format!(
+                "Group '{}' was removed, the core keeps its server until another one is selected",
+                name
+            ),
+            _ => format!("Group '{}' was removed", name),
+        };
+
+        CommandResponse::Ok(OkCommandResponse::Message(message))
+    }
+
+    /// Renames a group and the active selection that points at it.
+    pub fn rename(&self, name: &str, new_name: &str) -> CommandResponse {
+        if let Err(e) = self.storage.rename_group(name, new_name) {
+            return error(format!("Failed to rename group '{}': {}", name, e));
+        }
+
+        match self.config_service.load_active() {
+            Some(mut active) if active.group == name => {
+                active.group = new_name.to_string();
+                if let Err(e) = self.config_service.save_active(&active) {
+                    return error(format!(
+                        "Group '{}' was renamed to '{}', but the active selection was not: {}",
+                        name, new_name, e
+                    ));
+                }
+            }
+            _ => {}
+        }
+
+        CommandResponse::Ok(OkCommandResponse::Message(format!(
+            "Group '{}' was renamed to '{}'",
+            name, new_name
+        )))
+    }
+
+    pub async fn refresh(&self, name: &str) -> CommandResponse {
+        match refresh_group(&self.storage, name).await {
+            Ok(count) => CommandResponse::Ok(OkCommandResponse::Message(format!(
+                "Group '{}' was refreshed, {} configs",
+                name, count
+            ))),
+            Err(e) => error(e.to_string()),
+        }
+    }
+}
+
+fn error(message: String) -> CommandResponse {
+    CommandResponse::Err(ErrorCommandResponse::Message(message))
+}
+
+fn rejected_lines(rejected: &[RejectedLine]) -> String {
+    rejected
+        .iter()
+        .map(|line| format!("\n  line {}: {}", line.line, line.message))
+        .collect()
+}
@@ -1,6 +1,12 @@
+pub mod backend;
 pub mod config;
+pub mod groups;
+pub mod selection;
+pub mod settings;
 pub mod status;
 pub mod storage;
+pub mod subscriptions;
+pub mod watch;
 pub mod xray;
 
-pub use {config::*, status::*, storage::*};
+pub use {config::*, groups::*, selection::*, settings::*, status::*, storage::*};
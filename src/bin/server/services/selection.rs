@@ -0,0 +1,474 @@
+use luxnulla::{
+    ActiveSelection, CommandResponse, CoreState, ErrorCommandResponse, OkCommandResponse,
+    ServerRef,
+};
+use std::{io, path::PathBuf, sync::Arc};
+
+use crate::{
+    common::parsers::proxy_config::{Core, ProxyConfig},
+    http::services::config_builder::ConfigBuilderError,
+    services::{
+        ConfigService, Group, StorageError, StorageService,
+        backend::{ApplyError, BackendError, ConfigStore, CoreSupervisor},
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SelectError {
+    #[error("Group '{0}' not found")]
+    GroupNotFound(String),
+
+    #[error("Group '{group}' has no server {server}")]
+    ServerNotFound { group: String, server: ServerRef },
+
+    #[error("No server is selected")]
+    NothingSelected,
+
+    #[error("Group '{group}' has no other server {core} can run")]
+    NoOtherServer { group: String, core: Core },
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+
+    #[error(transparent)]
+    Build(#[from] ConfigBuilderError),
+
+    #[error(transparent)]
+    Apply(#[from] ApplyError),
+
+    #[error("Failed to save the selection: {0}")]
+    Save(#[from] io::Error),
+}
+
+pub struct Selected {
+    pub active: ActiveSelection,
+    /// Outcome of restarting the core with the new config.
+    pub core: CommandResponse,
+    /// Servers passed over while cycling because the core rejected their config.
+    pub skipped: usize,
+}
+
+impl Selected {
+    pub fn into_response(self) -> CommandResponse {
+        let mut message = format!("Selected {}", self.active);
+        if self.skipped > 0 {
+            message.push_str(&format!(" (skipped {} rejected)", self.skipped));
+        }
+
+        match self.core {
+            CommandResponse::Ok(OkCommandResponse::Message(restarted)) => CommandResponse::Ok(
+                OkCommandResponse::Message(format!("{}, {}", message, restarted)),
+            ),
+            CommandResponse::Err(ErrorCommandResponse::Message(e)) => CommandResponse::Err(
+                ErrorCommandResponse::Message(format!("{}, but {}", message, e)),
+            ),
+            response => response,
+        }
+    }
+}
+
+/// Switches the server the core routes through. The core config is generated for the
+/// server, the selection is saved to `active.json` and the core is restarted.
+pub struct Selector {
+    config_service: ConfigService,
+    storage: Arc<StorageService>,
+    store: Arc<ConfigStore>,
+    supervisor: Arc<CoreSupervisor>,
+}
+
+impl Selector {
+    pub fn new(
+        config_dir: PathBuf,
+        storage: Arc<StorageService>,
+        store: Arc<ConfigStore>,
+        supervisor: Arc<CoreSupervisor>,
+    ) -> Self {
+        Self {
+            config_service: ConfigService::new(config_dir),
+            storage,
+            store,
+            supervisor,
+        }
+    }
+
+    pub fn active(&self) -> Option<ActiveSelection> {
+        self.config_service.load_active()
+    }
+
+    /// Selects `server` of `group`, the first server when it is `None`. A name that matches
+    /// no server exactly may differ in case.
+    pub async fn select(
+        &self,
+        group: &str,
+        server: Option<&ServerRef>,
+    ) -> Result<Selected, SelectError> {
+        let group = self.group(group)?;
+        let configs = group.proxy_configs()?;
+
+        let index = match server {
+            None => Some(0),
+            Some(ServerRef::Index(index)) => Some(*index),
+            Some(ServerRef::Name(name)) => configs
+                .iter()
+                .position(|config| config.name() == Some(name.as_str()))
+                .or_else(|| {
+                    configs.iter().position(|config| {
+                        config
+                            .name()
+                            .is_some_and(|server| server.eq_ignore_ascii_case(name))
+                    })
+                }),
+        };
+        let Some(config) = index.and_then(|index| configs.get(index)) else {
+            return Err(SelectError::ServerNotFound {
+                group: group.name,
+                server: server.cloned().unwrap_or(ServerRef::Index(0)),
+            });
+        };
+
+        self.activate(&group.name, index.unwrap_or_default(), config)
+            .await
+    }
+
+    /// Moves to the next server of the active group, or the previous one, wrapping around
+    /// and skipping servers the core cannot run or rejects.
+    pub async fn step(&self, forward: bool) -> Result<Selected, SelectError> {
+        let active = self.active().ok_or(SelectError::NothingSelected)?;
+        let group = self.group(&active.group)?;
+        let configs = group.proxy_configs()?;
+        let core = self.store.backend().core();
+
+        let count = configs.len();
+        let current = locate(&active, &configs).unwrap_or(active.index % count.max(1));
+        let candidates = (1..count)
+            .map(|step| {
+                if forward {
+                    (current + step) % count
+                } else {
+                    (current + count - step) % count
+                }
+            })
+            .filter(|index| configs[*index].is_compatible_with(core));
+
+        let mut rejected = None;
+        let mut skipped = 0;
+        for index in candidates {
+            match self.activate(&group.name, index, &configs[index]).await {
+                Ok(selected) => return Ok(Selected { skipped, ..selected }),
+                Err(
+                    e @ SelectError::Apply(
+                        ApplyError::Backend(BackendError::InvalidConfig { .. })
+                        | ApplyError::Build(
+                            ConfigBuilderError::UnsupportedTransport { .. }
+                            | ConfigBuilderError::IncompatibleCore { .. },
+                        ),
+                    ),
+                ) => {
+                    rejected = Some(e);
+                    skipped += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(rejected.unwrap_or(SelectError::NoOtherServer {
+            group: group.name,
+            core,
+        }))
+    }
+
+    /// Rebuilds the core config from the active selection and restarts the core when it
+    /// runs, so new inbounds or log level take effect.
+    pub async fn regenerate(&self) -> Result<String, String> {
+        let file = self.store.backend().config_file().to_string();
+
+        let Some(active) = self.active() else {
+            return Ok(format!(
+                "no server is selected, {} is generated on the next selection",
+                file
+            ));
+        };
+
+        let group = self.group(&active.group).map_err(|e| match e {
+            SelectError::GroupNotFound(name) => {
+                format!("group '{}' of the active server is gone", name)
+            }
+            e => e.to_string(),
+        })?;
+        let configs = group.proxy_configs().map_err(|e| e.to_string())?;
+        let Some(index) = locate(&active, &configs) else {
+            return Err(format!(
+                "server {} of group '{}' is gone",
+                active.server.map_or(ServerRef::Index(active.index), ServerRef::Name),
+                group.name
+            ));
+        };
+
+        self.store
+            .apply(&configs[index])
+            .await
+            .map_err(|e| format!("{} was not regenerated: {}", file, e))?;
+
+        // a refreshed group may have moved the server or dropped it
+        let current = ActiveSelection {
+            group: group.name,
+            index,
+            server: configs[index].name().map(String::from),
+        };
+        if current != active {
+            self.config_service
+                .save_active(&current)
+                .map_err(|e| format!("Failed to save the selection: {}", e))?;
+        }
+
+        if self.supervisor.status().state == CoreState::Stopped {
+            return Ok(format!("{} was regenerated", file));
+        }
+
+        match self.supervisor.restart().await {
+            CommandResponse::Ok(OkCommandResponse::Message(restarted)) => {
+                Ok(format!("{} was regenerated, {}", file, restarted))
+            }
+            CommandResponse::Err(ErrorCommandResponse::Message(e)) => {
+                Err(format!("{} was regenerated but {}", file, e))
+            }
+            _ => Ok(format!("{} was regenerated", file)),
+        }
+    }
+
+    fn group(&self, name: &str) -> Result<Group, SelectError> {
+        self.storage
+            .get_group(name)?
+            .ok_or_else(|| SelectError::GroupNotFound(name.to_string()))
+    }
+
+    async fn activate(
+        &self,
+        group: &str,
+        index: usize,
+        config: &ProxyConfig,
+    ) -> Result<Selected, SelectError> {
+        self.store.apply(config).await?;
+
+        let active = ActiveSelection {
+            group: group.to_string(),
+            index,
+            server: config.name().map(String::from),
+        };
+        self.config_service.save_active(&active)?;
+
+        // selecting a server means using it, a stopped core is started as well
+        let core = self.supervisor.restart().await;
+
+        Ok(Selected {
+            active,
+            core,
+            skipped: 0,
+        })
+    }
+}
+
+/// Position of the active server in `configs`. The saved name is looked up first, as a
+/// refreshed group may have moved the server, and the saved index is the fallback.
+fn locate(active: &ActiveSelection, configs: &[ProxyConfig]) -> Option<usize> {
+    active
+        .server
+        .as_deref()
+        .and_then(|server| configs.iter().position(|config| config.name() == Some(server)))
+        .or_else(|| (active.index < configs.len()).then_some(active.index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::parsers::proxy_config,
+        services::{
+            CoreSettings,
+            backend::fake::FakeBackend,
+            xray::logs::{LOG_BUFFER_LINES, LogBuffer},
+        },
+    };
+    use serde_json::json;
+    use std::{fs, path::Path};
+
+    fn group(name: &str, lines: &str) -> Group {
+        Group::new(name.to_string(), json!(proxy_config::work(lines).configs))
+    }
+
+    fn selector(backend: FakeBackend, config_dir: &Path, group: Group) -> Selector {
+        let backend = Arc::new(backend);
+        let store = Arc::new(ConfigStore::new(
+            backend.clone(),
+            CoreSettings::default(),
+            config_dir,
+        ));
+        let supervisor = Arc::new(CoreSupervisor::new(
+            backend,
+            store.config_path().to_path_buf(),
+            Arc::new(LogBuffer::new(LOG_BUFFER_LINES, None)),
+        ));
+        let storage = Arc::new(StorageService::new(config_dir.join("groups")));
+        storage.store_group(group).unwrap();
+
+        Selector::new(config_dir.to_path_buf(), storage, store, supervisor)
+    }
+
+    fn server(selected: &Selected) -> (usize, Option<&str>) {
+        (selected.active.index, selected.active.server.as_deref())
+    }
+
+    // the storage watcher blocks the thread it is spawned on
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn selects_by_index_and_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let selector = selector(
+            FakeBackend::new("exec sleep 30"),
+            dir.path(),
+            group(
+                "g",
+                "trojan://pass@one.com:443#one\n\
+                 trojan://pass@two.com:443#Two\n\
+                 trojan://pass@three.com:443#two",
+            ),
+        );
+
+        let selected = selector.select("g", None).await.unwrap();
+        assert_eq!(server(&selected), (0, Some("one")));
+        assert!(matches!(selected.core, CommandResponse::Ok(_)));
+
+        let selected = selector.select("g", Some(&ServerRef::Index(1))).await.unwrap();
+        assert_eq!(server(&selected), (1, Some("Two")));
+
+        // an exact match wins over one that differs in case
+        let by_name = ServerRef::Name(String::from("two"));
+        let selected = selector.select("g", Some(&by_name)).await.unwrap();
+        assert_eq!(server(&selected), (2, Some("two")));
+
+        let by_name = ServerRef::Name(String::from("ONE"));
+        let selected = selector.select("g", Some(&by_name)).await.unwrap();
+        assert_eq!(server(&selected), (0, Some("one")));
+        assert_eq!(selector.active(), Some(selected.active));
+
+        assert!(matches!(
+            selector.select("g", Some(&ServerRef::Index(3))).await,
+            Err(SelectError::ServerNotFound { .. })
+        ));
+        assert!(matches!(
+            selector.select("h", None).await,
+            Err(SelectError::GroupNotFound(_))
+        ));
+        assert_eq!(selector.active().unwrap().index, 0);
+
+        selector.supervisor.stop().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn steps_wrap_around_and_skip_unusable_servers() {
+        let dir = tempfile::tempdir().unwrap();
+        let selector = selector(
+            FakeBackend::new("exec sleep 30")
+                .rejecting_address("bad.com")
+                .unsupporting("xhttp"),
+            dir.path(),
+            group(
+                "g",
+                "trojan://pass@one.com:443#one\n\
+                 trojan://pass@bad.com:443#bad\n\
+                 trojan://pass@xhttp.com:443?type=xhttp#xhttp\n\
+                 hysteria2://pass@sing-box.com:443#sing-box\n\
+                 trojan://pass@four.com:443#four",
+            ),
+        );
+
+        assert!(matches!(
+            selector.step(true).await,
+            Err(SelectError::NothingSelected)
+        ));
+
+        selector.select("g", None).await.unwrap();
+
+        let selected = selector.step(true).await.unwrap();
+        assert_eq!(server(&selected), (4, Some("four")));
+        assert_eq!(selected.skipped, 2);
+
+        let selected = selector.step(true).await.unwrap();
+        assert_eq!(server(&selected), (0, Some("one")));
+        assert_eq!(selected.skipped, 0);
+
+        let selected = selector.step(false).await.unwrap();
+        assert_eq!(server(&selected), (4, Some("four")));
+
+        selector.supervisor.stop().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn step_fails_without_another_usable_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let selector = selector(
+            FakeBackend::new("exec sleep 30").rejecting_address("bad.com"),
+            dir.path(),
+            group(
+                "g",
+                "trojan://pass@one.com:443#one\n\
+                 hysteria2://pass@sing-box.com:443#sing-box",
+            ),
+        );
+        selector.select("g", None).await.unwrap();
+
+        assert!(matches!(
+            selector.step(true).await,
+            Err(SelectError::NoOtherServer { .. })
+        ));
+
+        let refreshed = group(
+            "g",
+            "trojan://pass@one.com:443#one\ntrojan://pass@bad.com:443#bad",
+        );
+        selector.storage.store_group(refreshed).unwrap();
+
+        assert!(matches!(
+            selector.step(false).await,
+            Err(SelectError::Apply(ApplyError::Backend(BackendError::InvalidConfig { .. })))
+        ));
+        assert_eq!(selector.active().unwrap().server.as_deref(), Some("one"));
+
+        selector.supervisor.stop().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn refreshed_group_keeps_the_selected_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let selector = selector(
+            FakeBackend::new("exec sleep 30"),
+            dir.path(),
+            group(
+                "g",
+                "trojan://pass@one.com:443#one\n\
+                 trojan://pass@two.com:443#two\n\
+                 trojan://pass@three.com:443#three",
+            ),
+        );
+        selector.select("g", Some(&ServerRef::Index(1))).await.unwrap();
+
+        let refreshed = group(
+            "g",
+            "trojan://pass@two.com:443#two\n\
+             trojan://pass@one.com:443#one\n\
+             trojan://pass@three.com:443#three",
+        );
+        selector.storage.store_group(refreshed).unwrap();
+
+        selector.regenerate().await.unwrap();
+        let active = selector.active().unwrap();
+        assert_eq!((active.index, active.server.as_deref()), (0, Some("two")));
+        assert!(fs::read_to_string(dir.path().join("fake.json"))
+            .unwrap()
+            .contains("two.com"));
+
+        let selected = selector.step(true).await.unwrap();
+        assert_eq!(server(&selected), (1, Some("one")));
+
+        selector.supervisor.stop().await;
+    }
+}
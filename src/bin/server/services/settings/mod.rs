@@ -0,0 +1,562 @@
+use kdl::{KdlDocument, KdlError, KdlNode, KdlValue};
+use luxnulla::{LUXNULLA_CONFIG_FILE, default_socket_path, framing::DEFAULT_MAX_MESSAGE_SIZE};
+use std::{
+    fmt::Display,
+    fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::{common::parsers::proxy_config::Core, services::backend::Profile};
+
+pub mod reload;
+
+pub use reload::*;
+
+// example luxnulla.kdl, every node is optional
+// http-bind "0.0.0.0:3000"
+// socket "/tmp/luxnulla-core.sock"
+// editor "zeditor"
+// storage-dir "groups"
+// log-level "warning"
+// cors-origins "http://localhost:5173" "https://luxnulla.example"
+// subscription-interval "6h"
+// max-message-size 16777216
+//
+// inbounds {
+//     listen "127.0.0.1"
+//     socks 10808
+//     http 10809
+// }
+//
+// active-profile "laptop"
+//
+// profile "laptop" {
+//     core "sing-box"
+//     binary "/opt/sing-box/sing-box"
+// }
+//
+// profile "server" {
+//     core "xray"
+// }
+
+pub const DEFAULT_HTTP_BIND: &str = "0.0.0.0:3000";
+
+/// Smallest `max-message-size`, below it an ordinary request would be refused.
+const MIN_MESSAGE_SIZE: usize = 4096;
+
+/// Keys read only when the daemon starts, the rest are applied when the file is saved.
+pub const RESTART_KEYS: &[&str] = &["http-bind", "socket", "storage-dir", "profile"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum SettingsError {
+    #[error("Failed to read settings: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("line {line}, column {column}: {message}")]
+    Invalid {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+/// Daemon settings read from `luxnulla.kdl`, relative paths are relative to the config dir.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub http_bind: SocketAddr,
+    pub socket_path: PathBuf,
+    /// Editor of `luxnulla edit` when neither `$VISUAL` nor `$EDITOR` is set.
+    pub editor: Option<String>,
+    pub storage_dir: PathBuf,
+    pub core: CoreSettings,
+    pub profile: Profile,
+    /// Origins allowed to call the HTTP API from a browser, any origin when empty.
+    pub cors_origins: Vec<String>,
+    /// How often groups created from a subscription URL are fetched again, never when unset.
+    pub subscription_interval: Option<Duration>,
+    /// Largest request accepted on the socket, in bytes.
+    pub max_message_size: usize,
+}
+
+/// Everything besides the selected server a generated core config is built from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreSettings {
+    pub log_level: LogLevel,
+    pub listen: String,
+    pub socks_port: u16,
+    pub http_port: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warning,
+    Error,
+    None,
+}
+
+impl Default for CoreSettings {
+    fn default() -> Self {
+        Self {
+            log_level: LogLevel::Warning,
+            listen: String::from("127.0.0.1"),
+            socks_port: 10808,
+            http_port: 10809,
+        }
+    }
+}
+
+impl LogLevel {
+    pub fn xray(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warning => "warning",
+            LogLevel::Error => "error",
+            LogLevel::None => "none",
+        }
+    }
+
+    /// sing-box has no `none` level, the log is disabled instead.
+    pub fn sing_box(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warning => "warn",
+            LogLevel::Error | LogLevel::None => "error",
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warning" | "warn" => Ok(LogLevel::Warning),
+            "error" => Ok(LogLevel::Error),
+            "none" => Ok(LogLevel::None),
+            _ => Err(format!(
+                "unknown log level '{}', expected debug, info, warning, error or none",
+                s
+            )),
+        }
+    }
+}
+
+impl Settings {
+    /// Defaults used for every setting the file leaves out.
+    pub fn new(config_dir: &Path) -> Self {
+        Self {
+            http_bind: DEFAULT_HTTP_BIND.parse().unwrap(),
+            socket_path: default_socket_path(),
+            editor: None,
+            storage_dir: config_dir.join("groups"),
+            core: CoreSettings::default(),
+            profile: Profile::default(),
+            cors_origins: Vec::new(),
+            subscription_interval: None,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+
+    /// Reads `luxnulla.kdl` from `config_dir`, a missing file means the defaults.
+    pub fn load(config_dir: &Path) -> Result<Self, SettingsError> {
+        match fs::read_to_string(config_dir.join(LUXNULLA_CONFIG_FILE)) {
+            Ok(content) => Self::parse(&content, config_dir),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::new(config_dir)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn parse(content: &str, config_dir: &Path) -> Result<Self, SettingsError> {
+        let source = Source { content };
+        let document = KdlDocument::parse(content).map_err(|e| source.kdl_error(e))?;
+
+        let mut settings = Self::new(config_dir);
+        let mut active_profile = None;
+        let mut profiles = Vec::new();
+
+        for node in document.nodes() {
+            match node.name().value() {
+                "http-bind" => settings.http_bind = source.parse(node)?,
+                "socket" => settings.socket_path = config_dir.join(source.string(node)?),
+                "editor" => settings.editor = Some(source.string(node)?.to_string()),
+                "storage-dir" => settings.storage_dir = config_dir.join(source.string(node)?),
+                "log-level" => settings.core.log_level = source.parse(node)?,
+                "cors-origins" => settings.cors_origins = source.strings(node)?,
+                "subscription-interval" => {
+                    settings.subscription_interval = source.interval(node)?
+                }
+                "max-message-size" => settings.max_message_size = source.size(node)?,
+                "inbounds" => {
+                    for child in source.children(node) {
+                        match child.name().value() {
+                            "listen" => settings.core.listen = source.string(child)?.to_string(),
+                            "socks" => settings.core.socks_port = source.port(child)?,
+                            "http" => settings.core.http_port = source.port(child)?,
+                            _ => return Err(source.unknown(child)),
+                        }
+                    }
+                }
+                "active-profile" => active_profile = Some(node),
+                "profile" => profiles.push(source.profile(node)?),
+                _ => return Err(source.unknown(node)),
+            }
+        }
+
+        if settings.core.socks_port == settings.core.http_port {
+            return Err(source.error(
+                document
+                    .nodes()
+                    .iter()
+                    .find(|node| node.name().value() == "inbounds"),
+                "socks and http inbounds cannot share a port",
+            ));
+        }
+
+        // the active profile, or the first one when it is not set
+        settings.profile = match active_profile {
+            Some(node) => {
+                let name = source.string(node)?;
+                profiles
+                    .into_iter()
+                    .find(|profile| profile.name == name)
+                    .ok_or_else(|| {
+                        source.error(Some(node), format!("profile '{}' is not defined", name))
+                    })?
+            }
+            None => profiles.into_iter().next().unwrap_or_default(),
+        };
+
+        Ok(settings)
+    }
+
+    /// Keys of `luxnulla.kdl` whose values differ from `other`.
+    pub fn changed_keys(&self, other: &Settings) -> Vec<&'static str> {
+        let mut keys = Vec::new();
+
+        if self.http_bind != other.http_bind {
+            keys.push("http-bind");
+        }
+        if self.socket_path != other.socket_path {
+            keys.push("socket");
+        }
+        if self.editor != other.editor {
+            keys.push("editor");
+        }
+        if self.storage_dir != other.storage_dir {
+            keys.push("storage-dir");
+        }
+        if self.core.log_level != other.core.log_level {
+            keys.push("log-level");
+        }
+        if (&self.core.listen, self.core.socks_port, self.core.http_port)
+            != (&other.core.listen, other.core.socks_port, other.core.http_port)
+        {
+            keys.push("inbounds");
+        }
+        if self.profile != other.profile {
+            keys.push("profile");
+        }
+        if self.cors_origins != other.cors_origins {
+            keys.push("cors-origins");
+        }
+        if self.subscription_interval != other.subscription_interval {
+            keys.push("subscription-interval");
+        }
+        if self.max_message_size != other.max_message_size {
+            keys.push("max-message-size");
+        }
+
+        keys
+    }
+}
+
+/// Turns nodes into values, errors point at the line and column of the offending node.
+struct Source<'a> {
+    content: &'a str,
+}
+
+impl Source<'_> {
+    fn profile(&self, node: &KdlNode) -> Result<Profile, SettingsError> {
+        let mut profile = Profile {
+            name: self.string(node)?.to_string(),
+            ..Default::default()
+        };
+
+        for child in self.children(node) {
+            match child.name().value() {
+                "core" => profile.core = self.parse::<Core>(child)?,
+                "binary" => profile.binary = Some(PathBuf::from(self.string(child)?)),
+                _ => return Err(self.unknown(child)),
+            }
+        }
+
+        Ok(profile)
+    }
+
+    fn children<'n>(&self, node: &'n KdlNode) -> &'n [KdlNode] {
+        node.children().map(KdlDocument::nodes).unwrap_or_default()
+    }
+
+    fn string<'n>(&self, node: &'n KdlNode) -> Result<&'n str, SettingsError> {
+        node.get(0).and_then(KdlValue::as_string).ok_or_else(|| {
+            self.error(
+                Some(node),
+                format!("'{}' expects a string", node.name().value()),
+            )
+        })
+    }
+
+    fn strings(&self, node: &KdlNode) -> Result<Vec<String>, SettingsError> {
+        node.entries()
+            .iter()
+            .map(|entry| {
+                entry.value().as_string().map(String::from).ok_or_else(|| {
+                    self.error(
+                        Some(node),
+                        format!("'{}' expects strings", node.name().value()),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// A number with a unit, `90s`, `30m`, `6h` or `1d`, or `off`.
+    fn interval(&self, node: &KdlNode) -> Result<Option<Duration>, SettingsError> {
+        let value = self.string(node)?;
+        if value == "off" {
+            return Ok(None);
+        }
+
+        let (amount, unit) = value.split_at(value.len().saturating_sub(1));
+        let seconds = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 3_600,
+            "d" => 86_400,
+            _ => 0,
+        };
+
+        match amount.parse::<u64>() {
+            Ok(amount) if amount > 0 && seconds > 0 => {
+                Ok(Some(Duration::from_secs(amount * seconds)))
+            }
+            _ => Err(self.error(
+                Some(node),
+                format!(
+                    "invalid '{}': expected an interval such as 30m, 6h or 1d, or off",
+                    node.name().value()
+                ),
+            )),
+        }
+    }
+
+    /// A size in bytes, at least `MIN_MESSAGE_SIZE`.
+    fn size(&self, node: &KdlNode) -> Result<usize, SettingsError> {
+        node.get(0)
+            .and_then(KdlValue::as_integer)
+            .and_then(|size| usize::try_from(size).ok())
+            .filter(|size| *size >= MIN_MESSAGE_SIZE)
+            .ok_or_else(|| {
+                self.error(
+                    Some(node),
+                    format!(
+                        "'{}' expects a size in bytes of at least {}",
+                        node.name().value(),
+                        MIN_MESSAGE_SIZE
+                    ),
+                )
+            })
+    }
+
+    fn port(&self, node: &KdlNode) -> Result<u16, SettingsError> {
+        node.get(0)
+            .and_then(KdlValue::as_integer)
+            .and_then(|port| u16::try_from(port).ok())
+            .filter(|port| *port != 0)
+            .ok_or_else(|| {
+                self.error(
+                    Some(node),
+                    format!(
+                        "'{}' expects a port between 1 and 65535",
+                        node.name().value()
+                    ),
+                )
+            })
+    }
+
+    fn parse<T>(&self, node: &KdlNode) -> Result<T, SettingsError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.string(node)?.parse().map_err(|e| {
+            self.error(
+                Some(node),
+                format!("invalid '{}': {}", node.name().value(), e),
+            )
+        })
+    }
+
+    fn unknown(&self, node: &KdlNode) -> SettingsError {
+        self.error(
+            Some(node),
+            format!("unknown setting '{}'", node.name().value()),
+        )
+    }
+
+    fn error(&self, node: Option<&KdlNode>, message: impl Into<String>) -> SettingsError {
+        let offset = node.map_or(0, |node| node.name().span().offset());
+        self.at(offset, message.into())
+    }
+
+    fn kdl_error(&self, error: KdlError) -> SettingsError {
+        match error.diagnostics.first() {
+            Some(diagnostic) => self.at(
+                diagnostic.span.offset(),
+                diagnostic
+                    .message
+                    .clone()
+                    .or_else(|| diagnostic.label.clone())
+                    .unwrap_or_else(|| String::from("invalid KDL")),
+            ),
+            None => self.at(0, String::from("invalid KDL")),
+        }
+    }
+
+    fn at(&self, offset: usize, message: String) -> SettingsError {
+        let offset = (0..=offset.min(self.content.len()))
+            .rev()
+            .find(|offset| self.content.is_char_boundary(*offset))
+            .unwrap_or(0);
+        let before = &self.content[..offset];
+
+        SettingsError::Invalid {
+            line: before.matches('\n').count() + 1,
+            column: before.rsplit('\n').next().unwrap_or("").chars().count() + 1,
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Result<Settings, SettingsError> {
+        Settings::parse(content, Path::new("/config"))
+    }
+
+    fn position(result: Result<Settings, SettingsError>) -> (usize, usize) {
+        match result {
+            Err(SettingsError::Invalid { line, column, .. }) => (line, column),
+            other => panic!("expected an invalid settings error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_values_are_defaults() {
+        assert_eq!(parse("").unwrap(), Settings::new(Path::new("/config")));
+    }
+
+    #[test]
+    fn parses_every_setting() {
+        let settings = parse(
+            r#"
+http-bind "127.0.0.1:8080"
+socket "/run/luxnulla.sock"
+editor "vim"
+storage-dir "subscriptions"
+log-level "debug"
+cors-origins "http://localhost:5173" "https://luxnulla.example"
+subscription-interval "6h"
+max-message-size 1048576
+inbounds {
+    listen "0.0.0.0"
+    socks 1080
+    http 8118
+}
+active-profile "server"
+profile "laptop" {
+    core "sing-box"
+}
+profile "server" {
+    binary "/opt/xray/xray"
+}
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.http_bind, "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(settings.socket_path, PathBuf::from("/run/luxnulla.sock"));
+        assert_eq!(settings.editor.as_deref(), Some("vim"));
+        assert_eq!(settings.storage_dir, PathBuf::from("/config/subscriptions"));
+        assert_eq!(
+            settings.core,
+            CoreSettings {
+                log_level: LogLevel::Debug,
+                listen: String::from("0.0.0.0"),
+                socks_port: 1080,
+                http_port: 8118,
+            }
+        );
+        assert_eq!(
+            settings.cors_origins,
+            ["http://localhost:5173", "https://luxnulla.example"]
+        );
+        assert_eq!(
+            settings.subscription_interval,
+            Some(Duration::from_secs(6 * 3_600))
+        );
+        assert_eq!(settings.max_message_size, 1_048_576);
+        assert_eq!(settings.profile.name, "server");
+        assert_eq!(settings.profile.core, Core::Xray);
+        assert_eq!(
+            settings.profile.binary,
+            Some(PathBuf::from("/opt/xray/xray"))
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_node() {
+        assert_eq!(position(parse("editor \"vim\"\n  htp-bind \"x\"")), (2, 3));
+        assert_eq!(position(parse("inbounds {\n    socks 70000\n}")), (2, 5));
+        assert_eq!(position(parse("log-level \"loud\"")), (1, 1));
+        assert_eq!(
+            position(parse("profile \"a\"\nactive-profile \"b\"")),
+            (2, 1)
+        );
+        assert_eq!(position(parse("editor \"vim\n")), (1, 8));
+        assert_eq!(position(parse("subscription-interval \"6 hours\"")), (1, 1));
+        assert_eq!(position(parse("max-message-size 100")), (1, 1));
+    }
+
+    #[test]
+    fn reports_changed_keys() {
+        let running = parse("editor \"vim\"").unwrap();
+        let edited = parse(
+            r#"
+editor "nano"
+socket "/run/lux.sock"
+inbounds {
+    socks 1080
+}
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            running.changed_keys(&edited),
+            ["socket", "editor", "inbounds"]
+        );
+        assert!(running.changed_keys(&running.clone()).is_empty());
+    }
+}
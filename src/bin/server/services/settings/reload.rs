@@ -0,0 +1,148 @@
+use luxnulla::{CommandResponse, ErrorCommandResponse, LUXNULLA_CONFIG_FILE, OkCommandResponse};
+use std::{fs, path::PathBuf, sync::Arc};
+use tokio::sync::{broadcast, watch};
+
+use crate::services::{RESTART_KEYS, Selector, Settings, backend::ConfigStore, watch::watch_file};
+
+/// Applies edits of `luxnulla.kdl` to the running daemon. Keys in `RESTART_KEYS` keep their
+/// running value and are reported until the daemon is restarted.
+pub struct SettingsWatcher {
+    config_dir: PathBuf,
+    settings: watch::Sender<Settings>,
+    store: Arc<ConfigStore>,
+    selector: Arc<Selector>,
+    notifications: broadcast::Sender<CommandResponse>,
+}
+
+impl SettingsWatcher {
+    pub fn new(
+        config_dir: PathBuf,
+        settings: Settings,
+        store: Arc<ConfigStore>,
+        selector: Arc<Selector>,
+        notifications: broadcast::Sender<CommandResponse>,
+    ) -> Self {
+        Self {
+            config_dir,
+            settings: watch::Sender::new(settings),
+            store,
+            selector,
+            notifications,
+        }
+    }
+
+    /// The running settings, updated as the file is reloaded.
+    pub fn settings(&self) -> watch::Receiver<Settings> {
+        self.settings.subscribe()
+    }
+
+    pub fn watch(self: Arc<Self>) -> notify::Result<()> {
+        let settings_path = self.config_dir.join(LUXNULLA_CONFIG_FILE);
+
+        watch_file(settings_path, move || {
+            let watcher = self.clone();
+            async move { watcher.reload().await }
+        })
+    }
+
+    /// Saves `luxnulla.kdl` edited by a client if it parses, and applies it right away.
+    pub async fn save(&self, content: &str) -> CommandResponse {
+        let settings = match Settings::parse(content, &self.config_dir) {
+            Ok(settings) => settings,
+            Err(e) => {
+                return CommandResponse::Err(ErrorCommandResponse::InvalidConfig(e.to_string()));
+            }
+        };
+
+        if let Err(e) = fs::write(self.config_dir.join(LUXNULLA_CONFIG_FILE), content) {
+            return CommandResponse::Err(ErrorCommandResponse::Message(format!(
+                "Failed to save {}: {}",
+                LUXNULLA_CONFIG_FILE, e
+            )));
+        }
+
+        // the watcher sees the write too, but finds nothing left to apply
+        self.apply(settings).await.unwrap_or_else(|| {
+            CommandResponse::Ok(OkCommandResponse::Message(format!(
+                "{} was saved, nothing the daemon uses changed",
+                LUXNULLA_CONFIG_FILE
+            )))
+        })
+    }
+
+    async fn reload(&self) {
+        let response = match Settings::load(&self.config_dir) {
+            Ok(settings) => match self.apply(settings).await {
+                Some(response) => response,
+                None => return,
+            },
+            Err(e) => CommandResponse::Err(ErrorCommandResponse::Message(format!(
+                "{} was not reloaded: {}",
+                LUXNULLA_CONFIG_FILE, e
+            ))),
+        };
+
+        match &response {
+            CommandResponse::Err(ErrorCommandResponse::Message(message)) => {
+                eprintln!("{}", message)
+            }
+            CommandResponse::Ok(OkCommandResponse::Message(message)) => println!("{}", message),
+            _ => {}
+        }
+
+        // nobody following is not an error
+        let _ = self.notifications.send(response);
+    }
+
+    /// Returns `None` when nothing the daemon uses has changed.
+    async fn apply(&self, mut settings: Settings) -> Option<CommandResponse> {
+        let running = self.settings.borrow().clone();
+        let changed = running.changed_keys(&settings);
+        if changed.is_empty() {
+            return None;
+        }
+
+        let (pending, applied): (Vec<_>, Vec<_>) =
+            changed.into_iter().partition(|key| RESTART_KEYS.contains(key));
+
+        settings.http_bind = running.http_bind;
+        settings.socket_path = running.socket_path.clone();
+        settings.storage_dir = running.storage_dir.clone();
+        settings.profile = running.profile.clone();
+
+        let core_changed = settings.core != running.core;
+        self.store.set_settings(settings.core.clone());
+        self.settings.send_replace(settings);
+
+        let mut message = if applied.is_empty() {
+            format!("{} was reloaded", LUXNULLA_CONFIG_FILE)
+        } else {
+            format!(
+                "{} was reloaded, applied {}",
+                LUXNULLA_CONFIG_FILE,
+                applied.join(", ")
+            )
+        };
+        if !pending.is_empty() {
+            message.push_str(&format!(
+                "; {} take effect after the daemon is restarted",
+                pending.join(", ")
+            ));
+        }
+
+        if !core_changed {
+            return Some(CommandResponse::Ok(OkCommandResponse::Message(message)));
+        }
+
+        Some(match self.selector.regenerate().await {
+            Ok(regenerated) => CommandResponse::Ok(OkCommandResponse::Message(format!(
+                "{}; {}",
+                message, regenerated
+            ))),
+            Err(e) => CommandResponse::Err(ErrorCommandResponse::Message(format!(
+                "{}; {}",
+                message, e
+            ))),
+        })
+    }
+}
@@ -1,15 +1,94 @@
-use luxnulla::{CommandResponse, OkCommandResponse};
+use crate::services::{ConfigService, Group, StorageService, backend::CoreSupervisor};
+use luxnulla::{
+    CommandResponse, DaemonStatus, ErrorCommandResponse, InboundStatus, OkCommandResponse,
+};
+use serde_json::Value as JsonValue;
+use std::{fs, net::SocketAddr, path::PathBuf, sync::Arc, time::Instant};
 
-pub struct StatusService;
+pub struct StatusService {
+    started_at: Instant,
+    http_bind: SocketAddr,
+    config_service: ConfigService,
+    storage: Arc<StorageService>,
+    core_supervisor: Arc<CoreSupervisor>,
+}
 
 impl StatusService {
-    pub fn new() -> Self {
-        Self
+    pub fn new(
+        config_dir: PathBuf,
+        storage: Arc<StorageService>,
+        core_supervisor: Arc<CoreSupervisor>,
+        http_bind: SocketAddr,
+    ) -> Self {
+        Self {
+            started_at: Instant::now(),
+            http_bind,
+            config_service: ConfigService::new(config_dir),
+            storage,
+            core_supervisor,
+        }
     }
 
     pub fn get_status(&self) -> CommandResponse {
-        CommandResponse::Ok(OkCommandResponse::Message(
-            "Luxnulla-core is running".to_string(),
-        ))
+        let mut groups = match self.storage.get_all_groups() {
+            Ok(groups) => groups.iter().map(Group::status).collect::<Vec<_>>(),
+            Err(e) => {
+                return CommandResponse::Err(ErrorCommandResponse::Message(format!(
+                    "Failed to read groups: {}",
+                    e
+                )));
+            }
+        };
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+
+        CommandResponse::Ok(OkCommandResponse::Status(Box::new(DaemonStatus {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            http_address: self.http_bind.to_string(),
+            core: self.core_supervisor.status(),
+            active: self.config_service.load_active(),
+            inbounds: self.inbounds(),
+            groups,
+        })))
+    }
+
+    /// Inbounds are read back from the core config so hand edits are reported as well,
+    /// sing-box names the fields `type` and `listen_port`.
+    fn inbounds(&self) -> Vec<InboundStatus> {
+        let Ok(content) = fs::read_to_string(self.core_supervisor.config_path()) else {
+            return Vec::new();
+        };
+        let Ok(config) = serde_json::from_str::<JsonValue>(&content) else {
+            return Vec::new();
+        };
+
+        config
+            .get("inbounds")
+            .and_then(JsonValue::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|inbound| {
+                Some(InboundStatus {
+                    tag: inbound.get("tag").and_then(JsonValue::as_str).map(String::from),
+                    protocol: inbound
+                        .get("protocol")
+                        .or_else(|| inbound.get("type"))?
+                        .as_str()?
+                        .to_string(),
+                    listen: inbound
+                        .get("listen")
+                        .and_then(JsonValue::as_str)
+                        .unwrap_or("0.0.0.0")
+                        .to_string(),
+                    port: u16::try_from(
+                        inbound
+                            .get("port")
+                            .or_else(|| inbound.get("listen_port"))?
+                            .as_u64()?,
+                    )
+                    .ok()?,
+                })
+            })
+            .collect()
     }
 }
@@ -1,5 +1,7 @@
+use crate::common::parsers::{proxy_config::ProxyConfig, sip008::Usage};
+use chrono::{DateTime, Utc};
 use eyre::OptionExt;
-use luxnulla::CONFIG_DIR;
+use luxnulla::{CONFIG_DIR, GroupStatus};
 use notify::{EventKind, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value as JsonValue, json};
@@ -12,11 +14,43 @@ use std::sync::{Arc, RwLock};
 pub struct Group {
     pub name: String,
     pub configs: JsonValue,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    /// Last time the configs were parsed from a subscription or payload.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refreshed_at: Option<DateTime<Utc>>,
+    /// Subscription URL the configs were fetched from, pasted configs have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
 }
 
 impl Group {
     pub fn new(name: String, configs: JsonValue) -> Self {
-        Self { name, configs }
+        Self {
+            name,
+            configs,
+            usage: None,
+            refreshed_at: None,
+            source: None,
+        }
+    }
+
+    pub fn config_count(&self) -> usize {
+        self.configs.as_array().map_or(0, Vec::len)
+    }
+
+    pub fn proxy_configs(&self) -> Result<Vec<ProxyConfig>, StorageError> {
+        serde_json::from_value(self.configs.clone())
+            .map_err(|e| StorageError::DeserializationError(e.to_string()))
+    }
+
+    pub fn status(&self) -> GroupStatus {
+        GroupStatus {
+            name: self.name.clone(),
+            configs: self.config_count(),
+            refreshed_at: self.refreshed_at,
+            source: self.source.clone(),
+        }
     }
 }
 
@@ -27,10 +61,7 @@ pub struct StorageService {
 }
 
 impl StorageService {
-    pub fn new() -> Self {
-        let config_dir_path = dirs::config_dir().unwrap().join(CONFIG_DIR);
-        let groups_dir = config_dir_path.join("groups");
-
+    pub fn new(groups_dir: PathBuf) -> Self {
         if !groups_dir.exists() {
             fs::create_dir_all(&groups_dir)
                 .unwrap_or_else(|e| panic!("Failed to create groups directory: {}", e));
@@ -154,6 +185,8 @@ impl StorageService {
     }
 
     pub fn store_group(&self, group: Group) -> Result<(), StorageError> {
+        check_group_name(&group.name)?;
+
         let group_name = group.name.clone();
         {
             let mut groups = self.groups.write().map_err(|_| StorageError::LockError)?;
@@ -163,6 +196,42 @@ impl StorageService {
         Ok(())
     }
 
+    /// Stores a new group, unlike `store_group` it does not replace one with the same name.
+    pub fn add_group(&self, group: Group) -> Result<(), StorageError> {
+        check_group_name(&group.name)?;
+
+        let group_name = group.name.clone();
+        {
+            let mut groups = self.groups.write().map_err(|_| StorageError::LockError)?;
+            if groups.contains_key(&group_name) {
+                return Err(StorageError::GroupExists(group_name));
+            }
+            groups.insert(group_name.clone(), group);
+        }
+        self.save_group_to_file(&group_name)
+    }
+
+    /// Moves a group and its file to `new_name`.
+    pub fn rename_group(&self, name: &str, new_name: &str) -> Result<(), StorageError> {
+        check_group_name(new_name)?;
+
+        {
+            let mut groups = self.groups.write().map_err(|_| StorageError::LockError)?;
+            if groups.contains_key(new_name) {
+                return Err(StorageError::GroupExists(new_name.to_string()));
+            }
+
+            let mut group = groups
+                .remove(name)
+                .ok_or_else(|| StorageError::GroupNotFound(name.to_string()))?;
+            group.name = new_name.to_string();
+            groups.insert(group.name.clone(), group);
+        }
+
+        self.save_group_to_file(new_name)?;
+        self.delete_group_file(name)
+    }
+
     pub fn get_group(&self, name: &str) -> Result<Option<Group>, StorageError> {
         let groups = self.groups.read().map_err(|_| StorageError::LockError)?;
         Ok(groups.get(name).cloned())
@@ -186,6 +255,9 @@ impl StorageService {
                     let updated_group = Group {
                         name: existing_group.name.clone(),
                         configs: group.configs.clone(),
+                        usage: existing_group.usage.clone(),
+                        refreshed_at: existing_group.refreshed_at,
+                        source: existing_group.source.clone(),
                     };
 
                     groups.insert(updated_group.name.clone(), updated_group);
@@ -241,6 +313,8 @@ impl StorageService {
     }
 
     pub fn upsert_group(&self, group: Group) -> Result<bool, StorageError> {
+        check_group_name(&group.name)?;
+
         let group_name = group.name.clone();
         let existed = {
             let mut groups = self.groups.write().map_err(|_| StorageError::LockError)?;
@@ -304,9 +378,18 @@ impl StorageService {
     }
 }
 
+/// Groups are stored as `<name>.json`, a name must stay inside the groups directory.
+fn check_group_name(name: &str) -> Result<(), StorageError> {
+    if name.trim().is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(StorageError::InvalidGroupName(name.to_string()));
+    }
+
+    Ok(())
+}
+
 impl Default for StorageService {
     fn default() -> Self {
-        Self::new()
+        Self::new(dirs::config_dir().unwrap().join(CONFIG_DIR).join("groups"))
     }
 }
 
@@ -318,6 +401,12 @@ pub enum StorageError {
     #[error("Group '{0}' not found")]
     GroupNotFound(String),
 
+    #[error("Group '{0}' already exists")]
+    GroupExists(String),
+
+    #[error("Invalid group name '{0}', it is empty, starts with a dot or has slashes")]
+    InvalidGroupName(String),
+
     #[error("Invalid JSON configuration: {0}")]
     InvalidJson(String),
 
@@ -333,3 +422,40 @@ pub enum StorageError {
     #[error("Deserialization error: {0}")]
     DeserializationError(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn renames_a_group_and_its_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = StorageService::new(dir.path().to_path_buf());
+
+        storage.add_group(Group::new(String::from("a"), json!([]))).unwrap();
+        storage.add_group(Group::new(String::from("b"), json!([]))).unwrap();
+
+        assert!(matches!(
+            storage.add_group(Group::new(String::from("a"), json!([]))),
+            Err(StorageError::GroupExists(_))
+        ));
+        assert!(matches!(
+            storage.rename_group("a", "b"),
+            Err(StorageError::GroupExists(_))
+        ));
+        assert!(matches!(
+            storage.rename_group("a", "../c"),
+            Err(StorageError::InvalidGroupName(_))
+        ));
+        assert!(matches!(
+            storage.store_group(Group::new(String::from("../c"), json!([]))),
+            Err(StorageError::InvalidGroupName(_))
+        ));
+
+        storage.rename_group("a", "c").unwrap();
+        assert!(storage.get_group("a").unwrap().is_none());
+        assert_eq!(storage.get_group("c").unwrap().unwrap().name, "c");
+        assert!(!dir.path().join("a.json").exists());
+        assert!(dir.path().join("c.json").exists());
+    }
+}
@@ -0,0 +1,132 @@
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::{collections::HashMap, future, sync::Arc, time::Duration};
+use tokio::sync::watch;
+
+use crate::services::{Settings, StorageError, StorageService, xray::fetcher::get_configs};
+
+/// Wait before a subscription that failed to fetch is tried again, unless the interval
+/// is shorter.
+const RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, thiserror::Error)]
+pub enum RefreshError {
+    #[error("Group '{0}' not found")]
+    GroupNotFound(String),
+
+    #[error("Group '{0}' was not created from a subscription URL")]
+    NoSource(String),
+
+    #[error("Failed to fetch {url}: {details}")]
+    Fetch { url: String, details: String },
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+/// Fetches the subscription of a group again and replaces its configs,
+/// returning how many were parsed.
+pub async fn refresh_group(storage: &StorageService, name: &str) -> Result<usize, RefreshError> {
+    let mut group = storage
+        .get_group(name)?
+        .ok_or_else(|| RefreshError::GroupNotFound(name.to_string()))?;
+    let url = group
+        .source
+        .clone()
+        .ok_or_else(|| RefreshError::NoSource(name.to_string()))?;
+
+    let report = get_configs(&url).await.map_err(|e| RefreshError::Fetch {
+        url: url.clone(),
+        details: e.to_string(),
+    })?;
+
+    group.configs = json!(report.configs);
+    group.usage = report.usage;
+    group.refreshed_at = Some(Utc::now());
+    storage.store_group(group)?;
+
+    Ok(report.configs.len())
+}
+
+/// Refreshes every group with a subscription URL once `subscription-interval` passed since
+/// its last refresh. The schedule follows the settings as they are reloaded.
+pub fn spawn_refresher(storage: Arc<StorageService>, mut settings: watch::Receiver<Settings>) {
+    tokio::spawn(async move {
+        let mut retry_at = HashMap::new();
+
+        loop {
+            let interval = settings.borrow_and_update().subscription_interval;
+            let wait = match interval {
+                Some(interval) => Some(refresh_due(&storage, interval, &mut retry_at).await),
+                None => None,
+            };
+
+            let sleep = async {
+                match wait {
+                    Some(wait) => tokio::time::sleep(wait).await,
+                    None => future::pending().await,
+                }
+            };
+
+            tokio::select! {
+                _ = sleep => {}
+                changed = settings.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Refreshes the groups that are due and returns the time until the next one is.
+async fn refresh_due(
+    storage: &StorageService,
+    interval: Duration,
+    retry_at: &mut HashMap<String, DateTime<Utc>>,
+) -> Duration {
+    let groups = match storage.get_all_groups() {
+        Ok(groups) => groups,
+        Err(e) => {
+            eprintln!("Warning: Could not read groups to refresh: {}", e);
+            return RETRY_AFTER.min(interval);
+        }
+    };
+
+    let interval_delta = chrono::Duration::from_std(interval).unwrap_or(chrono::Duration::MAX);
+    let now = Utc::now();
+    let mut next = interval;
+
+    for group in groups.iter().filter(|group| group.source.is_some()) {
+        let due = retry_at.get(&group.name).copied().unwrap_or_else(|| {
+            group
+                .refreshed_at
+                .and_then(|at| at.checked_add_signed(interval_delta))
+                .unwrap_or(now)
+        });
+
+        if due > now {
+            next = next.min((due - now).to_std().unwrap_or_default());
+            continue;
+        }
+
+        match refresh_group(storage, &group.name).await {
+            Ok(count) => {
+                retry_at.remove(&group.name);
+                println!("Refreshed group '{}', {} configs", group.name, count);
+            }
+            Err(e) => {
+                let retry = RETRY_AFTER.min(interval);
+                retry_at.insert(
+                    group.name.clone(),
+                    now + chrono::Duration::from_std(retry).unwrap_or_default(),
+                );
+                next = next.min(retry);
+                eprintln!("Warning: Could not refresh group '{}': {}", group.name, e);
+            }
+        }
+    }
+
+    next
+}
@@ -0,0 +1,53 @@
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::{future::Future, path::PathBuf, time::Duration};
+use tokio::sync::mpsc;
+
+/// Quiet period after the last write before a change is handled, editors often write
+/// a file several times while saving.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Runs `on_change` whenever the file at `path` is written. The directory is watched rather
+/// than the file, editors save by renaming a new file over the old one.
+pub fn watch_file<F, Fut>(path: PathBuf, on_change: F) -> notify::Result<()>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let mut watcher = notify::recommended_watcher({
+        let path = path.clone();
+        move |res: notify::Result<notify::Event>| {
+            let Ok(event) = res else { return };
+
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                && event.paths.iter().any(|changed| changed == &path)
+            {
+                let _ = tx.send(());
+            }
+        }
+    })?;
+
+    if let Some(dir) = path.parent() {
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    }
+
+    tokio::spawn(async move {
+        // the watch ends when the watcher is dropped
+        let _watcher = watcher;
+
+        while rx.recv().await.is_some() {
+            loop {
+                match tokio::time::timeout(DEBOUNCE, rx.recv()).await {
+                    Ok(Some(())) => continue,
+                    Ok(None) => return,
+                    Err(_) => break,
+                }
+            }
+
+            on_change().await;
+        }
+    });
+
+    Ok(())
+}
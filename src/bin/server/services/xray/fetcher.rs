@@ -1,9 +1,15 @@
 use crate::common::{
     fetchers,
-    parsers::{self, proxy_config::ProxyConfig},
+    parsers::{
+        self, clash, outbounds,
+        proxy_config::{self, ParseReport},
+        sip008,
+    },
 };
+use base64::{Engine, prelude::BASE64_STANDARD};
+use url::Url;
 
-pub async fn get_configs(url: &str) -> Result<Vec<ProxyConfig>, Box<dyn std::error::Error>> {
+pub async fn get_configs(url: &str) -> Result<ParseReport, Box<dyn std::error::Error>> {
     println!("--- Fetching from plain text URL: {} ---", url);
 
     let body = match fetchers::config::fetch(url).await {
@@ -14,24 +20,109 @@ pub async fn get_configs(url: &str) -> Result<Vec<ProxyConfig>, Box<dyn std::err
         }
     };
 
-    let raw_subs = match parsers::proxy_config::decode_config_from_base64(body.as_str()) {
-        Ok(subs) => subs,
-        Err(e) => {
-            eprintln!("Error decoding config: {}", e);
-            return Err(e);
-        }
+    let report = if sip008::is_sip008_config(&body) {
+        println!("INFO: Content detected as SIP008 JSON.");
+        sip008::work(&body)?
+    } else if outbounds::is_outbounds_config(&body) {
+        println!("INFO: Content detected as JSON outbounds.");
+        outbounds::work(&body)?
+    } else if clash::is_clash_config(&body) {
+        println!("INFO: Content detected as Clash YAML.");
+        clash::work(&body)?
+    } else {
+        let raw_subs = match parsers::proxy_config::decode_config_from_base64(body.as_str()) {
+            Ok(subs) => subs,
+            Err(e) => {
+                eprintln!("Error decoding config: {}", e);
+                return Err(e);
+            }
+        };
+
+        parsers::proxy_config::work(raw_subs.as_str())
     };
 
-    let subs = match parsers::proxy_config::work(raw_subs.as_str()) {
-        Ok(subs) => subs,
-        Err(e) => {
-            eprintln!("Error processing config: {:?}", e);
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Error processing config",
-            )));
+    println!(
+        "INFO: Parsed {} configs, rejected {} lines.",
+        report.accepted_count(),
+        report.rejected_count()
+    );
+
+    Ok(report)
+}
+
+pub enum ConfigType {
+    Raw,
+    Base64,
+    Url,
+    Clash,
+    Outbounds,
+    Sip008,
+}
+
+pub fn determine_config_type(config: &str) -> Result<ConfigType, std::io::Error> {
+    if proxy_config::is_supported_scheme(config) {
+        Ok(ConfigType::Raw)
+    } else if config.trim().starts_with("http") || config.trim().starts_with("https") {
+        Ok(ConfigType::Url)
+    } else if sip008::is_sip008_config(config) {
+        Ok(ConfigType::Sip008)
+    } else if outbounds::is_outbounds_config(config) {
+        Ok(ConfigType::Outbounds)
+    } else if clash::is_clash_config(config) {
+        Ok(ConfigType::Clash)
+    } else if BASE64_STANDARD.decode(config.trim()).is_ok() {
+        Ok(ConfigType::Base64)
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Invalid config",
+        ))
+    }
+}
+
+pub async fn process_config(payload: &str) -> Result<ParseReport, std::io::Error> {
+    match determine_config_type(payload)? {
+        ConfigType::Raw => {
+            if Url::parse(payload).is_ok() {
+                Ok(proxy_config::work(payload))
+            } else {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Invalid URL",
+                ))
+            }
         }
-    };
+        ConfigType::Base64 => {
+            let raw_config = proxy_config::decode_config_from_base64(payload);
 
-    Ok(subs)
+            if let Ok(config) = raw_config {
+                if Url::parse(&config).is_ok() {
+                    Ok(proxy_config::work(&config))
+                } else {
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "Invalid URL",
+                    ))
+                }
+            } else {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Invalid config",
+                ))
+            }
+        }
+        ConfigType::Clash => clash::work(payload).map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string())
+        }),
+        ConfigType::Outbounds => outbounds::work(payload).map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string())
+        }),
+        ConfigType::Sip008 => sip008::work(payload).map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string())
+        }),
+        ConfigType::Url => match get_configs(payload).await {
+            Ok(report) => Ok(report),
+            Err(_) => Err(std::io::Error::other("Failed to fetch configs")),
+        },
+    }
 }
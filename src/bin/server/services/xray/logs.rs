@@ -0,0 +1,129 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    sync::broadcast,
+};
+
+/// Number of recent lines kept in memory.
+pub const LOG_BUFFER_LINES: usize = 1000;
+
+/// The log file is moved to `<name>.1` once it grows past this size.
+const MAX_LOG_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Recent output of the core, shared by the supervisor that writes it and clients that read it.
+pub struct LogBuffer {
+    capacity: usize,
+    lines: Mutex<VecDeque<String>>,
+    sender: broadcast::Sender<String>,
+    file: Option<Mutex<LogFile>>,
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: Option<File>,
+    size: u64,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize, log_file: Option<PathBuf>) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+
+        Self {
+            capacity,
+            lines: Mutex::new(VecDeque::with_capacity(capacity)),
+            sender,
+            file: log_file.map(|path| {
+                Mutex::new(LogFile {
+                    path,
+                    file: None,
+                    size: 0,
+                })
+            }),
+        }
+    }
+
+    pub fn push(&self, line: String) {
+        if let Some(file) = &self.file {
+            file.lock().unwrap().write_line(&line);
+        }
+
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line.clone());
+
+        // nobody following is not an error
+        let _ = self.sender.send(line);
+    }
+
+    /// Returns up to `count` of the most recent lines, oldest first.
+    pub fn tail(&self, count: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        lines.iter().skip(lines.len().saturating_sub(count)).cloned().collect()
+    }
+
+    /// Like `tail`, plus a receiver for every line pushed after the returned ones.
+    pub fn follow(&self, count: usize) -> (Vec<String>, broadcast::Receiver<String>) {
+        let lines = self.lines.lock().unwrap();
+        let receiver = self.sender.subscribe();
+        let tail = lines.iter().skip(lines.len().saturating_sub(count)).cloned().collect();
+
+        (tail, receiver)
+    }
+
+    /// Reads a child pipe line by line until it is closed.
+    pub async fn capture<R>(&self, pipe: R)
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut reader = BufReader::new(pipe).lines();
+
+        while let Ok(Some(line)) = reader.next_line().await {
+            self.push(line);
+        }
+    }
+}
+
+impl LogFile {
+    fn write_line(&mut self, line: &str) {
+        if let Err(e) = self.try_write_line(line) {
+            eprintln!("Failed to write log file {:?}: {}", self.path, e);
+            self.file = None;
+        }
+    }
+
+    fn try_write_line(&mut self, line: &str) -> std::io::Result<()> {
+        if self.file.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            self.size = file.metadata()?.len();
+            self.file = Some(file);
+        }
+
+        if self.size >= MAX_LOG_FILE_SIZE {
+            let mut rotated = self.path.clone().into_os_string();
+            rotated.push(".1");
+
+            self.file = None;
+            fs::rename(&self.path, rotated)?;
+            self.file = Some(File::create(&self.path)?);
+            self.size = 0;
+        }
+
+        if let Some(file) = self.file.as_mut() {
+            writeln!(file, "{}", line)?;
+            self.size += line.len() as u64 + 1;
+        }
+
+        Ok(())
+    }
+}
@@ -1,4 +1,2 @@
-pub mod xray;
 pub mod fetcher;
-
-pub use xray::*;
+pub mod logs;
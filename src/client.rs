@@ -0,0 +1,453 @@
+use kdl::{KdlDocument, KdlValue};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::net::{
+    UnixStream,
+    unix::{OwnedReadHalf, OwnedWriteHalf},
+};
+
+use crate::{
+    CAPABILITY_EDIT_CONFIG, CAPABILITY_GROUPS, CAPABILITY_LOGS_FOLLOW, CAPABILITY_ROLLBACK,
+    CAPABILITY_SELECT, CONFIG_DIR, CommandRequest, CommandResponse, ConfigFile, DaemonStatus,
+    EditableConfig, ErrorCommandResponse, GroupDetails, GroupStatus, HandshakeError, Hello,
+    HelloResponse, LUXNULLA_CONFIG_FILE, OkCommandResponse, Request, Response, ServerRef,
+    default_socket_path,
+    framing::{FrameError, FrameReader, write_frame},
+};
+
+/// How long to wait for the daemon to accept a connection or answer a request, restarting
+/// the core can take a few seconds.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("could not connect to {}: {error}", .path.display())]
+    Connect { path: PathBuf, error: io::Error },
+
+    #[error(transparent)]
+    Handshake(#[from] HandshakeError),
+
+    #[error(transparent)]
+    Frame(#[from] FrameError),
+
+    #[error("the daemon did not answer within {} seconds", .0.as_secs())]
+    Timeout(Duration),
+
+    #[error("the daemon closed the connection")]
+    Closed,
+
+    #[error("luxnulla {version} does not support {capability}, update the daemon")]
+    Unsupported {
+        capability: &'static str,
+        version: String,
+    },
+
+    /// The daemon could not carry out the request.
+    #[error("{0}")]
+    Daemon(String),
+
+    /// An edited config was rejected, the message says why.
+    #[error("{0}")]
+    InvalidConfig(String),
+
+    #[error("the daemon sent a response this client does not expect")]
+    UnexpectedResponse,
+}
+
+/// Socket of the daemon, `socket` in luxnulla.kdl when it is set. A file that does not
+/// parse falls back to the default, the daemon refuses to start with it anyway.
+pub fn socket_path() -> PathBuf {
+    let Some(config_dir) = dirs::config_dir().map(|dir| dir.join(CONFIG_DIR)) else {
+        return default_socket_path();
+    };
+
+    fs::read_to_string(config_dir.join(LUXNULLA_CONFIG_FILE))
+        .ok()
+        .and_then(|content| content.parse::<KdlDocument>().ok())
+        .and_then(|document| {
+            document
+                .get_arg("socket")
+                .and_then(KdlValue::as_string)
+                .map(|socket| config_dir.join(socket))
+        })
+        .unwrap_or_else(default_socket_path)
+}
+
+/// A connection to the daemon. Requests are answered in the order they are sent, a request
+/// that timed out leaves the connection usable.
+pub struct Client {
+    reader: FrameReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+    daemon: Hello,
+    last_id: u64,
+    timeout: Duration,
+}
+
+impl Client {
+    pub async fn connect(socket: impl AsRef<Path>) -> Result<Self, ClientError> {
+        Self::connect_with_timeout(socket, DEFAULT_TIMEOUT).await
+    }
+
+    /// `timeout` applies to connecting and to every request.
+    pub async fn connect_with_timeout(
+        socket: impl AsRef<Path>,
+        timeout: Duration,
+    ) -> Result<Self, ClientError> {
+        let path = socket.as_ref();
+        let connect = async {
+            let sock = UnixStream::connect(path)
+                .await
+                .map_err(|error| ClientError::Connect {
+                    path: path.to_path_buf(),
+                    error,
+                })?;
+            let (reader, mut writer) = sock.into_split();
+            let mut reader = FrameReader::new(reader);
+
+            write_frame(&mut writer, &Hello::current()).await?;
+            let daemon = match reader.read().await? {
+                Some(HelloResponse::Ok(hello)) => hello,
+                Some(HelloResponse::Err(reason)) => {
+                    return Err(HandshakeError::Refused(reason).into());
+                }
+                None => return Err(ClientError::Closed),
+            };
+            daemon.check()?;
+
+            Ok(Self {
+                reader,
+                writer,
+                daemon,
+                last_id: 0,
+                timeout,
+            })
+        };
+
+        tokio::time::timeout(timeout, connect)
+            .await
+            .map_err(|_| ClientError::Timeout(timeout))?
+    }
+
+    /// Hello the daemon answered with.
+    pub fn daemon(&self) -> &Hello {
+        &self.daemon
+    }
+
+    /// Sends any request and returns the response as it is, the typed methods below
+    /// cover the usual ones.
+    pub async fn request(
+        &mut self,
+        request: CommandRequest,
+    ) -> Result<CommandResponse, ClientError> {
+        let id = self.send(request).await?;
+
+        let receive = async {
+            loop {
+                match self.reader.read::<Response>().await? {
+                    Some(response) if response.id == id => return Ok(response.response),
+                    // the late answer to a request that timed out
+                    Some(response) if response.id < id => continue,
+                    Some(_) => return Err(ClientError::UnexpectedResponse),
+                    None => return Err(ClientError::Closed),
+                }
+            }
+        };
+
+        tokio::time::timeout(self.timeout, receive)
+            .await
+            .map_err(|_| ClientError::Timeout(self.timeout))?
+    }
+
+    pub async fn status(&mut self) -> Result<DaemonStatus, ClientError> {
+        match self.request(CommandRequest::Status).await? {
+            CommandResponse::Ok(OkCommandResponse::Status(status)) => Ok(*status),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Groups with the number of configs in each, sorted by name.
+    pub async fn groups(&mut self) -> Result<Vec<GroupStatus>, ClientError> {
+        self.require(CAPABILITY_GROUPS)?;
+
+        match self.request(CommandRequest::ListGroups).await? {
+            CommandResponse::Ok(OkCommandResponse::Groups(groups)) => Ok(groups),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn group(&mut self, name: &str) -> Result<GroupDetails, ClientError> {
+        self.require(CAPABILITY_GROUPS)?;
+
+        let request = CommandRequest::ShowGroup {
+            name: name.to_string(),
+        };
+        match self.request(request).await? {
+            CommandResponse::Ok(OkCommandResponse::Group(group)) => Ok(group),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Creates a group from a subscription URL, which the daemon fetches, or from configs
+    /// in any supported format.
+    pub async fn add_group(&mut self, name: &str, payload: String) -> Result<String, ClientError> {
+        self.require(CAPABILITY_GROUPS)?;
+
+        let request = CommandRequest::AddGroup {
+            name: name.to_string(),
+            payload,
+        };
+        message(self.request(request).await?)
+    }
+
+    pub async fn remove_group(&mut self, name: &str) -> Result<String, ClientError> {
+        self.require(CAPABILITY_GROUPS)?;
+
+        let request = CommandRequest::RemoveGroup {
+            name: name.to_string(),
+        };
+        message(self.request(request).await?)
+    }
+
+    pub async fn rename_group(
+        &mut self,
+        name: &str,
+        new_name: &str,
+    ) -> Result<String, ClientError> {
+        self.require(CAPABILITY_GROUPS)?;
+
+        let request = CommandRequest::RenameGroup {
+            name: name.to_string(),
+            new_name: new_name.to_string(),
+        };
+        message(self.request(request).await?)
+    }
+
+    /// Fetches the subscription of a group again.
+    pub async fn refresh_group(&mut self, name: &str) -> Result<String, ClientError> {
+        self.require(CAPABILITY_GROUPS)?;
+
+        let request = CommandRequest::RefreshGroup {
+            name: name.to_string(),
+        };
+        message(self.request(request).await?)
+    }
+
+    /// Routes the core through a server of `group`, the first one without `server`. The
+    /// core is restarted with it, or started when it was stopped.
+    pub async fn select(
+        &mut self,
+        group: &str,
+        server: Option<ServerRef>,
+    ) -> Result<String, ClientError> {
+        self.require(CAPABILITY_SELECT)?;
+
+        let request = CommandRequest::Select {
+            group: group.to_string(),
+            server,
+        };
+        message(self.request(request).await?)
+    }
+
+    /// Selects the next server of the active group the core can run, wrapping around.
+    pub async fn select_next(&mut self) -> Result<String, ClientError> {
+        self.require(CAPABILITY_SELECT)?;
+
+        message(self.request(CommandRequest::SelectNext).await?)
+    }
+
+    pub async fn select_previous(&mut self) -> Result<String, ClientError> {
+        self.require(CAPABILITY_SELECT)?;
+
+        message(self.request(CommandRequest::SelectPrevious).await?)
+    }
+
+    pub async fn start(&mut self) -> Result<String, ClientError> {
+        message(self.request(CommandRequest::Start).await?)
+    }
+
+    pub async fn stop(&mut self) -> Result<String, ClientError> {
+        message(self.request(CommandRequest::Stop).await?)
+    }
+
+    pub async fn restart(&mut self) -> Result<String, ClientError> {
+        message(self.request(CommandRequest::Restart).await?)
+    }
+
+    /// Restores the core config that was live `steps` replacements ago.
+    pub async fn rollback(&mut self, steps: usize) -> Result<String, ClientError> {
+        self.require(CAPABILITY_ROLLBACK)?;
+        message(self.request(CommandRequest::Rollback { steps }).await?)
+    }
+
+    /// The last `lines` lines of the core log.
+    pub async fn logs(&mut self, lines: usize) -> Result<Vec<String>, ClientError> {
+        match self.request(CommandRequest::Logs { follow: false, lines }).await? {
+            CommandResponse::Ok(OkCommandResponse::Logs(lines)) => Ok(lines),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// The last `lines` lines of the core log followed by every new one. The connection
+    /// is only used for the stream from then on.
+    pub async fn follow_logs(mut self, lines: usize) -> Result<LogStream, ClientError> {
+        self.require(CAPABILITY_LOGS_FOLLOW)?;
+        let id = self.send(CommandRequest::Logs { follow: true, lines }).await?;
+
+        Ok(LogStream {
+            reader: self.reader,
+            _writer: self.writer,
+            id,
+        })
+    }
+
+    pub async fn read_config(&mut self, file: ConfigFile) -> Result<EditableConfig, ClientError> {
+        self.require(CAPABILITY_EDIT_CONFIG)?;
+
+        match self.request(CommandRequest::ReadConfig { file }).await? {
+            CommandResponse::Ok(OkCommandResponse::Config(config)) => Ok(config),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Replaces the file with `content` if the daemon accepts it, `ClientError::InvalidConfig`
+    /// tells why it did not.
+    pub async fn write_config(
+        &mut self,
+        file: ConfigFile,
+        content: String,
+    ) -> Result<String, ClientError> {
+        self.require(CAPABILITY_EDIT_CONFIG)?;
+        message(self.request(CommandRequest::WriteConfig { file, content }).await?)
+    }
+
+    fn require(&self, capability: &'static str) -> Result<(), ClientError> {
+        if self.daemon.supports(capability) {
+            return Ok(());
+        }
+
+        Err(ClientError::Unsupported {
+            capability,
+            version: self.daemon.version.clone(),
+        })
+    }
+
+    /// Returns the ID the responses to `request` carry.
+    async fn send(&mut self, request: CommandRequest) -> Result<u64, ClientError> {
+        self.last_id += 1;
+        let id = self.last_id;
+
+        write_frame(&mut self.writer, &Request { id, request }).await?;
+
+        Ok(id)
+    }
+}
+
+/// Something the daemon sent while following the log.
+#[derive(Debug, Clone)]
+pub enum LogEvent {
+    Lines(Vec<String>),
+    /// A notification such as a reloaded config.
+    Message(String),
+    Error(String),
+}
+
+pub struct LogStream {
+    reader: FrameReader<OwnedReadHalf>,
+    // the daemon may take a closed write side for a client that is gone
+    _writer: OwnedWriteHalf,
+    id: u64,
+}
+
+impl LogStream {
+    /// Waits for the next event, `None` once the daemon closed the connection.
+    pub async fn next(&mut self) -> Result<Option<LogEvent>, ClientError> {
+        loop {
+            let Some(response) = self.reader.read::<Response>().await? else {
+                return Ok(None);
+            };
+            if response.id != self.id {
+                continue;
+            }
+
+            return Ok(Some(match response.response {
+                CommandResponse::Ok(OkCommandResponse::Logs(lines)) => LogEvent::Lines(lines),
+                CommandResponse::Ok(OkCommandResponse::Message(message)) => {
+                    LogEvent::Message(message)
+                }
+                CommandResponse::Err(
+                    ErrorCommandResponse::Message(error)
+                    | ErrorCommandResponse::InvalidConfig(error),
+                ) => LogEvent::Error(error),
+                _ => return Err(ClientError::UnexpectedResponse),
+            }));
+        }
+    }
+}
+
+fn message(response: CommandResponse) -> Result<String, ClientError> {
+    match response {
+        CommandResponse::Ok(OkCommandResponse::Message(message)) => Ok(message),
+        response => Err(unexpected(response)),
+    }
+}
+
+/// The error a response carries, or `UnexpectedResponse` when it is not one.
+fn unexpected(response: CommandResponse) -> ClientError {
+    match response {
+        CommandResponse::Err(ErrorCommandResponse::Message(error)) => ClientError::Daemon(error),
+        CommandResponse::Err(ErrorCommandResponse::InvalidConfig(error)) => {
+            ClientError::InvalidConfig(error)
+        }
+        CommandResponse::Ok(_) => ClientError::UnexpectedResponse,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::UnixListener;
+
+    #[tokio::test]
+    async fn answer_after_a_timeout_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let daemon = tokio::spawn(async move {
+            let (sock, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = sock.into_split();
+            let mut reader = FrameReader::new(reader);
+
+            reader.read::<Hello>().await.unwrap().unwrap();
+            write_frame(&mut writer, &HelloResponse::Ok(Hello::current()))
+                .await
+                .unwrap();
+
+            for delay in [150, 0] {
+                let request = reader.read::<Request>().await.unwrap().unwrap();
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+
+                let response = Response {
+                    id: request.id,
+                    response: CommandResponse::Ok(OkCommandResponse::Message(format!(
+                        "answer to {}",
+                        request.id
+                    ))),
+                };
+                write_frame(&mut writer, &response).await.unwrap();
+            }
+        });
+
+        let mut client = Client::connect_with_timeout(&path, Duration::from_millis(100))
+            .await
+            .unwrap();
+
+        assert!(matches!(client.restart().await, Err(ClientError::Timeout(_))));
+        assert_eq!(client.restart().await.unwrap(), "answer to 2");
+
+        daemon.await.unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+use serde::{Serialize, de::DeserializeOwned};
+use std::io;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+// Messages on the socket are newline-delimited JSON: one document per line, in both
+// directions. serde_json escapes newlines inside strings, so a line is always a whole message.
+
+/// Largest message accepted when no other limit is set.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum FrameError {
+    #[error("message is larger than {limit} bytes")]
+    TooLarge { limit: usize },
+
+    #[error("invalid message: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Reads messages one line at a time, several can arrive on one connection.
+pub struct FrameReader<R> {
+    reader: BufReader<R>,
+    max_size: usize,
+    /// Part of a message that has been read so far.
+    line: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_max_size(reader, DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    pub fn with_max_size(reader: R, max_size: usize) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            max_size,
+            line: Vec::new(),
+        }
+    }
+
+    /// Next message, `None` once the other side closed the connection. After `TooLarge`
+    /// the rest of the connection cannot be read.
+    ///
+    /// Cancelling a read, e.g. on a timeout, keeps what was read of the message and the
+    /// next call goes on from there.
+    pub async fn read<T: DeserializeOwned>(&mut self) -> Result<Option<T>, FrameError> {
+        loop {
+            // one byte over the limit tells a message of exactly `max_size` from a longer one
+            let limit = (self.max_size + 2).saturating_sub(self.line.len());
+            let read = (&mut self.reader)
+                .take(limit as u64)
+                .read_until(b'\n', &mut self.line)
+                .await?;
+            if read == 0 && self.line.is_empty() {
+                return Ok(None);
+            }
+
+            let mut line = std::mem::take(&mut self.line);
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            } else if read == limit {
+                return Err(FrameError::TooLarge {
+                    limit: self.max_size,
+                });
+            }
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+
+            if line.len() > self.max_size {
+                return Err(FrameError::TooLarge {
+                    limit: self.max_size,
+                });
+            }
+
+            // blank lines carry nothing, the peer may send them to keep a connection alive
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+
+            return Ok(Some(serde_json::from_slice(&line)?));
+        }
+    }
+}
+
+/// Writes `message` as one line.
+pub async fn write_frame<W, T>(writer: &mut W, message: &T) -> Result<(), FrameError>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let mut output = serde_json::to_vec(message)?;
+    output.push(b'\n');
+
+    writer.write_all(&output).await?;
+    writer.flush().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn reads_several_messages() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut reader = FrameReader::new(server);
+
+        // larger than the pipe, so the message arrives in several reads
+        let large = json!({ "content": "x\n".repeat(1000) });
+        let writer = tokio::spawn(async move {
+            write_frame(&mut client, &json!("first")).await.unwrap();
+            write_frame(&mut client, &large).await.unwrap();
+            client.write_all(b"\n\"last\"").await.unwrap();
+        });
+
+        assert_eq!(reader.read::<Value>().await.unwrap(), Some(json!("first")));
+        let message = reader.read::<Value>().await.unwrap().unwrap();
+        assert_eq!(message["content"].as_str().unwrap().len(), 2000);
+        assert_eq!(reader.read::<Value>().await.unwrap(), Some(json!("last")));
+        assert_eq!(reader.read::<Value>().await.unwrap(), None);
+
+        writer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_messages_over_the_limit() {
+        let mut reader = FrameReader::with_max_size(&b"\"1234\"\n\"12345\"\n"[..], 6);
+
+        assert_eq!(reader.read::<Value>().await.unwrap(), Some(json!("1234")));
+        assert!(matches!(
+            reader.read::<Value>().await,
+            Err(FrameError::TooLarge { limit: 6 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn cancelled_read_keeps_the_partial_message() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut reader = FrameReader::new(server);
+
+        client.write_all(b"{\"a\":").await.unwrap();
+        let read = tokio::time::timeout(Duration::from_millis(10), reader.read::<Value>()).await;
+        assert!(read.is_err());
+
+        client.write_all(b"1}\n").await.unwrap();
+        assert_eq!(reader.read::<Value>().await.unwrap(), Some(json!({ "a": 1 })));
+    }
+}
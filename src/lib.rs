@@ -1,35 +1,315 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+pub mod client;
+pub mod framing;
 
 pub const CONFIG_DIR: &str = "luxnulla";
 
 pub const LUXNULLA_CONFIG_FILE: &str = "luxnulla.kdl";
 pub const XRAY_CONFIG_FILE: &str = "xray.json";
+pub const SING_BOX_CONFIG_FILE: &str = "sing-box.json";
+pub const XRAY_LOG_FILE: &str = "xray.log";
+pub const ACTIVE_FILE: &str = "active.json";
 
 pub const SOCKET_NAME: &str = "luxnulla-core.sock";
-pub const EDITOR_NAME: &str = "zeditor";
+
+/// Where the daemon listens unless `socket` is set in luxnulla.kdl.
+pub fn default_socket_path() -> PathBuf {
+    PathBuf::from("/tmp").join(SOCKET_NAME)
+}
+
+/// Bumped whenever a message changes in a way the other side can not read.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Features of the socket protocol beyond plain requests, a client checks them before
+/// using a feature an older daemon may lack.
+pub const CAPABILITY_LOGS_FOLLOW: &str = "logs-follow";
+pub const CAPABILITY_EDIT_CONFIG: &str = "edit-config";
+pub const CAPABILITY_ROLLBACK: &str = "rollback";
+pub const CAPABILITY_GROUPS: &str = "groups";
+pub const CAPABILITY_SELECT: &str = "select";
+
+pub const CAPABILITIES: &[&str] = &[
+    CAPABILITY_LOGS_FOLLOW,
+    CAPABILITY_EDIT_CONFIG,
+    CAPABILITY_ROLLBACK,
+    CAPABILITY_GROUPS,
+    CAPABILITY_SELECT,
+];
+
+/// First message on a connection. The client sends its own, the daemon answers with
+/// a `HelloResponse` before any request is read.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Hello {
+    pub protocol: u32,
+    /// Package version, only shown to the user.
+    pub version: String,
+    pub capabilities: Vec<String>,
+}
+
+impl Hello {
+    /// The hello of this build.
+    pub fn current() -> Self {
+        Self {
+            protocol: PROTOCOL_VERSION,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    /// Whether this build can talk to the peer that sent `self`.
+    pub fn check(&self) -> Result<(), HandshakeError> {
+        if self.protocol == PROTOCOL_VERSION {
+            return Ok(());
+        }
+
+        Err(HandshakeError::Incompatible {
+            ours: PROTOCOL_VERSION,
+            our_version: env!("CARGO_PKG_VERSION").to_string(),
+            theirs: self.protocol,
+            version: self.version.clone(),
+        })
+    }
+
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum HelloResponse {
+    Ok(Hello),
+    /// The daemon closes the connection after sending it.
+    Err(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HandshakeError {
+    #[error(
+        "luxnulla {version} speaks protocol {theirs} and luxnulla {our_version} speaks {ours}, \
+         run a client and a daemon of the same version"
+    )]
+    Incompatible {
+        ours: u32,
+        our_version: String,
+        theirs: u32,
+        version: String,
+    },
+
+    #[error("the daemon refused the connection: {0}")]
+    Refused(String),
+}
+
+/// A request with the ID its responses carry.
+#[derive(Deserialize, Serialize)]
+pub struct Request {
+    pub id: u64,
+    pub request: CommandRequest,
+}
+
+/// A response to the request with the same ID. Streams such as `Logs { follow: true }`
+/// send many responses to one request.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Response {
+    pub id: u64,
+    pub response: CommandResponse,
+}
+
+/// Files a client can edit, the core config is the one of the active profile.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub enum ConfigFile {
+    Core,
+    Luxnulla,
+}
 
 #[derive(Deserialize, Serialize)]
 pub enum CommandRequest {
-    EditXray,
-    EditLuxnulla,
+    ReadConfig { file: ConfigFile },
+    /// Replaces the file if `content` passes validation.
+    WriteConfig { file: ConfigFile, content: String },
     Status,
+    Start,
+    Stop,
     Restart,
+    /// Restores the config that was live `steps` replacements ago.
+    Rollback { steps: usize },
+    Logs { follow: bool, lines: usize },
+    ListGroups,
+    ShowGroup { name: String },
+    /// `payload` is a subscription URL or configs in any format the HTTP API accepts.
+    AddGroup { name: String, payload: String },
+    RemoveGroup { name: String },
+    RenameGroup { name: String, new_name: String },
+    /// Fetches the subscription of the group again.
+    RefreshGroup { name: String },
+    /// Routes the core through a server of `group`, the first one without `server`.
+    Select {
+        group: String,
+        server: Option<ServerRef>,
+    },
+    /// Moves the selection to the next server of the active group that the core can run.
+    SelectNext,
+    SelectPrevious,
 }
 
-#[derive(Deserialize, Serialize)]
+/// A server of a group, by its index or its name.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ServerRef {
+    Index(usize),
+    Name(String),
+}
+
+impl std::str::FromStr for ServerRef {
+    type Err = std::convert::Infallible;
+
+    /// A number is an index, anything else a name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.parse() {
+            Ok(index) => ServerRef::Index(index),
+            Err(_) => ServerRef::Name(s.to_string()),
+        })
+    }
+}
+
+impl std::fmt::Display for ServerRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServerRef::Index(index) => write!(f, "#{}", index),
+            ServerRef::Name(name) => write!(f, "'{}'", name),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub enum CommandResponse {
     Ok(OkCommandResponse),
     Err(ErrorCommandResponse),
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub enum OkCommandResponse {
     Message(String),
-    GetSubs(Vec<String>),
+    Groups(Vec<GroupStatus>),
+    Group(GroupDetails),
+    Logs(Vec<String>),
+    Status(Box<DaemonStatus>),
+    Config(EditableConfig),
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub enum ErrorCommandResponse {
     Message(String),
-    GetSubs(String),
+    /// The edited content was rejected, the client can let the user fix it.
+    InvalidConfig(String),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EditableConfig {
+    /// File name, editors pick the syntax from its extension.
+    pub name: String,
+    pub content: String,
+    /// `editor` of luxnulla.kdl, used when neither `$VISUAL` nor `$EDITOR` is set.
+    pub editor: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DaemonStatus {
+    pub version: String,
+    pub uptime_secs: u64,
+    pub http_address: String,
+    pub core: CoreStatus,
+    pub active: Option<ActiveSelection>,
+    pub inbounds: Vec<InboundStatus>,
+    pub groups: Vec<GroupStatus>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CoreState {
+    Stopped,
+    Running,
+    Restarting,
+    BackingOff,
+}
+
+impl std::fmt::Display for CoreState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CoreState::Stopped => write!(f, "stopped"),
+            CoreState::Running => write!(f, "running"),
+            CoreState::Restarting => write!(f, "restarting"),
+            CoreState::BackingOff => write!(f, "backing off"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CoreStatus {
+    /// Name of the core selected by the active profile, `xray` or `sing-box`.
+    pub backend: String,
+    /// First line of the core's version output, queried when it is started.
+    pub version: Option<String>,
+    pub state: CoreState,
+    pub pid: Option<u32>,
+    pub uptime_secs: Option<u64>,
+    pub restart_count: u32,
+    pub crash_count: u32,
+    pub last_exit: Option<String>,
+}
+
+/// Group and server the generated core config routes through.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ActiveSelection {
+    pub group: String,
+    pub index: usize,
+    pub server: Option<String>,
+}
+
+impl std::fmt::Display for ActiveSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.server {
+            Some(server) => write!(f, "{} / {}", self.group, server),
+            None => write!(f, "{} / #{}", self.group, self.index),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct InboundStatus {
+    pub tag: Option<String>,
+    pub protocol: String,
+    pub listen: String,
+    pub port: u16,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GroupStatus {
+    pub name: String,
+    pub configs: usize,
+    pub refreshed_at: Option<DateTime<Utc>>,
+    /// Subscription URL, groups made of pasted configs have none.
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+/// A group with its servers, in the order their indices refer to.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GroupDetails {
+    pub name: String,
+    pub source: Option<String>,
+    pub refreshed_at: Option<DateTime<Utc>>,
+    pub servers: Vec<ServerSummary>,
+    /// Index of the active server when it is in this group.
+    pub active: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ServerSummary {
+    pub name: Option<String>,
+    pub protocol: String,
+    pub address: String,
+    pub port: u16,
 }